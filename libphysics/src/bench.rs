@@ -1,5 +1,8 @@
 extern crate test;
 
+use test::Bencher;
+use crate::fluid_sim::FluidSim;
+
 #[bench]
 fn fluid_sim(b: &mut Bencher) {
     const GRID_SIZE: usize = 3000;
@@ -10,6 +13,6 @@ fn fluid_sim(b: &mut Bencher) {
     fs.add_particles(&particles);
 
     b.iter(|| {
-        fluid_sim.update(0.001);
+        fs.update(0.001);
     });
 }
\ No newline at end of file
@@ -0,0 +1,56 @@
+use core_simd::*;
+use serde::{Serialize, Deserialize};
+use crate::shape::Shape;
+use crate::particle::Particle;
+use crate::vector_2::*;
+use crate::fluid_sim::Properties;
+use crate::scene::SceneShape;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Circle {
+    #[serde(with = "serde_vec2")]
+    pub pos: f32x2,
+    pub radius: f32
+}
+
+impl Shape for Circle {
+    fn collide_with(&self, particle: &mut Particle, properties: &Properties) {
+        let delta = particle.pos - self.pos;
+        let dist_sqrd = length_squared(delta);
+        let min_dist = self.radius + properties.radius;
+        if dist_sqrd <= 0.0 || dist_sqrd >= min_dist * min_dist {
+            return;
+        }
+
+        let dist = dist_sqrd.sqrt();
+        let normal = delta / vec2_from_single(dist);
+
+        // only reflect if the particle is moving into the circle
+        if dot(particle.vel, normal) < 0.0 {
+            particle.vel = reflect(particle.vel, normal);
+        }
+
+        // push the particle back out to the surface
+        particle.pos += normal * vec2_from_single(min_dist - dist);
+    }
+
+    fn contains(&self, pt: f32x2) -> bool {
+        return length_squared(pt - self.pos) <= self.radius * self.radius;
+    }
+
+    fn aabb(&self) -> (f32x2, f32x2) {
+        let r = vec2_from_single(self.radius);
+        return (self.pos - r, self.pos + r);
+    }
+
+    fn signed_distance(&self, pt: f32x2) -> (f32, f32x2) {
+        let delta = pt - self.pos;
+        let dist = length_squared(delta).sqrt();
+        let normal = if dist > 0.0 { delta / vec2_from_single(dist) } else { vec2(0.0, -1.0) };
+        return (dist - self.radius, normal);
+    }
+
+    fn scene_shape(&self) -> Option<SceneShape> {
+        return Some(SceneShape::Circle(self.clone()));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,118 @@
+use core_simd::*;
+use crate::particle::{Particle, ParticleId};
+use crate::matrix_2::Mat2;
+use crate::fluid_sim::Properties;
+use crate::vector_2::*;
+
+pub type ClusterId = usize;
+
+// a group of particles held together by shape matching, for jelly blocks and crumbling walls
+// the particles are still normal particles so they interact with everything else through the contacts
+// Muller et al. 2005 "Meshless Deformations Based on Shape Matching"
+// https://matthias-research.github.io/pages/publications/MeshlessDeformations_SIG05.pdf
+#[derive(Clone)]
+pub struct Cluster {
+    pub particles: Vec<ParticleId>,
+    pub rest_positions: Vec<f32x2>, // where each particle sits in the undeformed shape
+    pub stiffness: f32, // 0 to 1, how far particles are pulled towards their goal each step, 1 is rigid
+    pub linear: f32, // 0 to 1, how much the goal shape can stretch and shear, 0 only allows rotation
+    pub break_strain: Option<f32>, // particles pulled further than this many particle widths from their goal break off
+}
+
+impl Cluster {
+    pub fn new(particles: &[Particle], stiffness: f32) -> Cluster {
+        Cluster {
+            particles: particles.iter().map(|particle| particle.id).collect(),
+            rest_positions: particles.iter().map(|particle| particle.pos).collect(),
+            stiffness,
+            linear: 0.0,
+            break_strain: None
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.particles.len() < 2;
+    }
+
+    // particles must be sorted by id, which the FluidSim guarantees
+    // returns the ids of any particles that broke off
+    pub fn update(&mut self, particles: &mut Vec<Particle>, properties: &Properties, dt: f32) -> Vec<ParticleId> {
+        let mut broken: Vec<ParticleId> = Vec::new();
+
+        // forget about any particles that have been removed from the sim
+        let mut indices: Vec<usize> = Vec::new();
+        let mut i = 0;
+        while i < self.particles.len() {
+            match particles.binary_search_by_key(&self.particles[i], |particle| particle.id) {
+                Ok(index) => {
+                    indices.push(index);
+                    i += 1;
+                },
+                Err(_) => {
+                    self.particles.swap_remove(i);
+                    self.rest_positions.swap_remove(i);
+                }
+            }
+        }
+
+        if self.is_empty() {
+            self.rest_positions.clear();
+            return self.particles.drain(..).collect();
+        }
+
+        let count = vec2_from_single(indices.len() as f32);
+        let centre = indices.iter().fold(vec2_from_single(0.0), |sum, index| sum + particles[*index].pos) / count;
+        let rest_centre = self.rest_positions.iter().fold(vec2_from_single(0.0), |sum, pos| sum + *pos) / count;
+
+        // find the best rotation (and linear transform) from the rest shape to the current shape
+        let mut apq = Mat2::zero();
+        let mut aqq = Mat2::zero();
+        for (index, rest_pos) in indices.iter().zip(self.rest_positions.iter()) {
+            let p = particles[*index].pos - centre;
+            let q = *rest_pos - rest_centre;
+            apq = apq + Mat2::outer(p, q);
+            aqq = aqq + Mat2::outer(q, q);
+        }
+
+        let rotation = apq.polar_rotation();
+        let mut transform = rotation;
+        if self.linear > 0.0 {
+            if let Some(aqq_inv) = aqq.inverse() {
+                // preserve the volume
+                let linear = apq * aqq_inv;
+                let det = linear.determinant();
+                if det > 0.0 {
+                    let linear = linear.scale(1.0 / det.sqrt());
+                    transform = linear.scale(self.linear) + rotation.scale(1.0 - self.linear);
+                }
+            }
+        }
+
+        let particle_width = properties.radius * 2.0;
+        for (i, index) in indices.iter().enumerate() {
+            let particle = &mut particles[*index];
+            let goal = transform.mul_vec(self.rest_positions[i] - rest_centre) + centre;
+            let delta = goal - particle.pos;
+
+            if let Some(break_strain) = self.break_strain {
+                if length_squared(delta).sqrt() / particle_width > break_strain {
+                    broken.push(particle.id);
+                    continue;
+                }
+            }
+
+            let correction = delta * vec2_from_single(self.stiffness);
+            particle.pos += correction;
+            particle.vel += correction / vec2_from_single(dt);
+        }
+
+        for id in broken.iter() {
+            if let Some(i) = self.particles.iter().position(|particle| particle == id) {
+                self.particles.swap_remove(i);
+                self.rest_positions.swap_remove(i);
+            }
+        }
+
+        return broken;
+    }
+}
\ No newline at end of file
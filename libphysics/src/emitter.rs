@@ -0,0 +1,94 @@
+use core_simd::*;
+use rand::Rng;
+use rand::rngs::StdRng;
+use serde::{Serialize, Deserialize};
+use crate::particle::Particle;
+use crate::material::MaterialId;
+use crate::vector_2::*;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum EmitterShape {
+    Point(#[serde(with = "serde_vec2")] f32x2),
+    Line(#[serde(with = "serde_vec2")] f32x2, #[serde(with = "serde_vec2")] f32x2), // start and end points, particles spawn anywhere along the line
+    Area(#[serde(with = "serde_vec2")] f32x2, #[serde(with = "serde_vec2")] f32x2), // centre and size of an axis aligned box
+}
+
+// spawns particles at a fixed rate, used for taps and fountains
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Emitter {
+    pub shape: EmitterShape,
+    pub rate: f32, // particles per second
+    #[serde(with = "serde_vec2")]
+    pub velocity: f32x2, // initial velocity
+    #[serde(default)]
+    pub spread: f32, // radians, the initial velocity is randomly rotated within +/- half of this
+    #[serde(default)]
+    pub material: MaterialId,
+    #[serde(default)]
+    pub max_count: Option<usize>, // stop once this many particles have been emitted
+    #[serde(default = "enabled_default")]
+    pub enabled: bool,
+
+    #[serde(default)]
+    pub emitted: usize,
+    #[serde(default)]
+    pub accumulator: f32, // fractional particles carried over to the next step
+}
+
+fn enabled_default() -> bool {
+    return true;
+}
+
+impl Emitter {
+    pub fn new(shape: EmitterShape, rate: f32, velocity: f32x2) -> Emitter {
+        Emitter {
+            shape,
+            rate,
+            velocity,
+            spread: 0.0,
+            material: 0,
+            max_count: None,
+            enabled: true,
+            emitted: 0,
+            accumulator: 0.0
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        return match self.max_count {
+            Some(max_count) => self.emitted >= max_count,
+            None => false
+        };
+    }
+
+    pub fn emit(&mut self, dt: f32, rng: &mut StdRng) -> Vec<Particle> {
+        let mut particles: Vec<Particle> = Vec::new();
+        if !self.enabled || self.is_finished() {
+            return particles;
+        }
+
+        self.accumulator += self.rate * dt;
+        let mut count = self.accumulator as usize;
+        self.accumulator -= count as f32;
+
+        if let Some(max_count) = self.max_count {
+            count = count.min(max_count - self.emitted);
+        }
+
+        for _i in 0..count {
+            let pos = match self.shape {
+                EmitterShape::Point(pos) => pos,
+                EmitterShape::Line(start, end) => start + (end - start) * vec2_from_single(rng.gen::<f32>()),
+                EmitterShape::Area(centre, size) => centre + size * vec2(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5),
+            };
+
+            let angle = (rng.gen::<f32>() - 0.5) * self.spread;
+            let mut particle = Particle::with_vel(pos, rotate_vector(self.velocity, angle));
+            particle.material = self.material;
+            particles.push(particle);
+        }
+
+        self.emitted += count;
+        return particles;
+    }
+}
\ No newline at end of file
@@ -0,0 +1,184 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use crate::fluid_sim::FluidSim;
+
+// per particle attributes written for each frame: id, position, velocity, contact count and density
+// everything is written as ascii so it can be read by ParaView (vtk, ply) or Python (csv)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Vtk, // legacy vtk polydata
+    Ply, // point cloud
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn from_extension(extension: &str) -> Option<ExportFormat> {
+        return match extension.to_lowercase().as_str() {
+            "vtk" => Some(ExportFormat::Vtk),
+            "ply" => Some(ExportFormat::Ply),
+            "csv" => Some(ExportFormat::Csv),
+            _ => None
+        };
+    }
+
+    pub fn extension(&self) -> &'static str {
+        return match self {
+            ExportFormat::Vtk => "vtk",
+            ExportFormat::Ply => "ply",
+            ExportFormat::Csv => "csv",
+        };
+    }
+
+    pub fn write_file(&self, fluid_sim: &FluidSim, path: &Path) -> Result<(), String> {
+        let file = File::create(path).map_err(|e| format!("failed to create {}: {}", path.display(), e))?;
+        let mut writer = BufWriter::new(file);
+        self.write(fluid_sim, &mut writer).map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+        return writer.flush().map_err(|e| format!("failed to write {}: {}", path.display(), e));
+    }
+
+    pub fn write(&self, fluid_sim: &FluidSim, writer: &mut dyn Write) -> std::io::Result<()> {
+        return match self {
+            ExportFormat::Vtk => ExportFormat::write_vtk(fluid_sim, writer),
+            ExportFormat::Ply => ExportFormat::write_ply(fluid_sim, writer),
+            ExportFormat::Csv => ExportFormat::write_csv(fluid_sim, writer),
+        };
+    }
+
+    // https://docs.vtk.org/en/latest/design_documents/VTKFileFormats.html#simple-legacy-formats
+    fn write_vtk(fluid_sim: &FluidSim, writer: &mut dyn Write) -> std::io::Result<()> {
+        let particles = &fluid_sim.particles;
+        let count = particles.len();
+
+        writeln!(writer, "# vtk DataFile Version 3.0")?;
+        writeln!(writer, "fluidic space particles")?;
+        writeln!(writer, "ASCII")?;
+        writeln!(writer, "DATASET POLYDATA")?;
+
+        // vtk is 3d, so z is always 0
+        writeln!(writer, "POINTS {} float", count)?;
+        for particle in particles.iter() {
+            writeln!(writer, "{} {} 0", particle.pos[0], particle.pos[1])?;
+        }
+
+        // a vertex cell per particle so they show up without needing a glyph filter
+        writeln!(writer, "VERTICES {} {}", count, count * 2)?;
+        for i in 0..count {
+            writeln!(writer, "1 {}", i)?;
+        }
+
+        writeln!(writer, "POINT_DATA {}", count)?;
+        writeln!(writer, "SCALARS id int 1")?;
+        writeln!(writer, "LOOKUP_TABLE default")?;
+        for particle in particles.iter() {
+            writeln!(writer, "{}", particle.id)?;
+        }
+
+        writeln!(writer, "VECTORS velocity float")?;
+        for particle in particles.iter() {
+            writeln!(writer, "{} {} 0", particle.vel[0], particle.vel[1])?;
+        }
+
+        writeln!(writer, "SCALARS contacts int 1")?;
+        writeln!(writer, "LOOKUP_TABLE default")?;
+        for particle in particles.iter() {
+            writeln!(writer, "{}", particle.contacts.len())?;
+        }
+
+        writeln!(writer, "SCALARS density float 1")?;
+        writeln!(writer, "LOOKUP_TABLE default")?;
+        for particle in particles.iter() {
+            writeln!(writer, "{}", particle.density)?;
+        }
+
+        return Ok(());
+    }
+
+    // http://paulbourke.net/dataformats/ply/
+    fn write_ply(fluid_sim: &FluidSim, writer: &mut dyn Write) -> std::io::Result<()> {
+        writeln!(writer, "ply")?;
+        writeln!(writer, "format ascii 1.0")?;
+        writeln!(writer, "comment fluidic space particles")?;
+        writeln!(writer, "element vertex {}", fluid_sim.particles.len())?;
+        writeln!(writer, "property float x")?;
+        writeln!(writer, "property float y")?;
+        writeln!(writer, "property float z")?;
+        writeln!(writer, "property float vx")?;
+        writeln!(writer, "property float vy")?;
+        writeln!(writer, "property uint id")?;
+        writeln!(writer, "property uint contacts")?;
+        writeln!(writer, "property float density")?;
+        writeln!(writer, "end_header")?;
+
+        for particle in fluid_sim.particles.iter() {
+            writeln!(writer, "{} {} 0 {} {} {} {} {}", particle.pos[0], particle.pos[1], particle.vel[0], particle.vel[1], particle.id, particle.contacts.len(), particle.density)?;
+        }
+
+        return Ok(());
+    }
+
+    fn write_csv(fluid_sim: &FluidSim, writer: &mut dyn Write) -> std::io::Result<()> {
+        writeln!(writer, "id,x,y,vx,vy,contacts,density")?;
+        for particle in fluid_sim.particles.iter() {
+            writeln!(writer, "{},{},{},{},{},{},{}", particle.id, particle.pos[0], particle.pos[1], particle.vel[0], particle.vel[1], particle.contacts.len(), particle.density)?;
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::exporter::ExportFormat;
+    use crate::fluid_sim::FluidSim;
+    use crate::particle::Particle;
+    use crate::vector_2::*;
+
+    // writes a sim with 2 particles and reads back the lines
+    fn export(format: ExportFormat) -> Vec<String> {
+        let path = std::env::temp_dir().join(format!("fluidic_space_export_{}.{}", std::process::id(), format.extension()));
+        let mut fluid_sim = FluidSim::with_seed(20, 10, 0);
+        fluid_sim.add_particles(&vec![Particle::with_vel(vec2(5.0, 5.0), vec2(1.0, 0.0)), Particle::new(vec2(15.5, 2.0))]);
+        format.write_file(&fluid_sim, &path).unwrap();
+        let lines = fs::read_to_string(&path).unwrap().lines().map(|line| line.to_string()).collect();
+        fs::remove_file(&path).unwrap();
+        return lines;
+    }
+
+    #[test]
+    fn writes_vtk() {
+        let lines = export(ExportFormat::Vtk);
+        assert_eq!(lines[0], "# vtk DataFile Version 3.0");
+        assert_eq!(lines[3], "DATASET POLYDATA");
+        assert_eq!(lines[4], "POINTS 2 float");
+        assert_eq!(lines[5], "5 5 0");
+        assert_eq!(lines[6], "15.5 2 0");
+        assert_eq!(lines[7], "VERTICES 2 4");
+        assert_eq!(lines[10], "POINT_DATA 2");
+        // id, contacts and density have a scalars and lookup table line, velocity just a vectors line, then a row per particle
+        assert_eq!(lines.len(), 11 + 4 + 3 + 4 + 4);
+    }
+
+    #[test]
+    fn writes_ply() {
+        let lines = export(ExportFormat::Ply);
+        assert_eq!(lines[0], "ply");
+        assert_eq!(lines[1], "format ascii 1.0");
+        assert_eq!(lines[3], "element vertex 2");
+        let end_header = lines.iter().position(|line| line == "end_header").unwrap();
+        let properties = lines.iter().filter(|line| line.starts_with("property ")).count();
+        assert_eq!(lines.len(), end_header + 3);
+        assert_eq!(lines[end_header + 1].split(' ').count(), properties);
+        assert_eq!(lines[end_header + 1], "5 5 0 1 0 0 0 0");
+    }
+
+    #[test]
+    fn writes_csv() {
+        let lines = export(ExportFormat::Csv);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "id,x,y,vx,vy,contacts,density");
+        assert_eq!(lines[1], "0,5,5,1,0,0,0");
+        assert_eq!(lines[2], "1,15.5,2,0,0,0,0");
+    }
+}
\ No newline at end of file
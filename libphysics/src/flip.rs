@@ -0,0 +1,489 @@
+use core_simd::*;
+use crate::particle::Particle;
+use crate::material::Material;
+use crate::matrix_2::Mat2;
+use crate::rect::Rect;
+use crate::shape::Shape;
+use crate::force_field::ForceField;
+use crate::spatial_hash::SpatialHash;
+use crate::fluid_sim::Properties;
+use crate::vector_2::*;
+
+// grid based FLIP/PIC/APIC liquid solver for large volumes where particle contacts are too expensive
+// particles carry the velocity, the MAC grid is only used to make the velocity field divergence free
+// https://www.cs.ubc.ca/~rbridson/fluidsimulation/fluids_notes.pdf
+// https://www.math.ucla.edu/~jteran/papers/JSSTS15.pdf (APIC)
+//
+// velocities are stored on a staggered grid:
+//  u (x velocity) at the centre of the left/right faces, (nx + 1) * ny
+//  v (y velocity) at the centre of the top/bottom faces, nx * (ny + 1)
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CellType {
+    Air,
+    Fluid,
+    Solid
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FlipTransfer {
+    FlipPic, // blend of FLIP and PIC using flip_ratio
+    Apic // affine particle in cell, low dissipation without FLIP noise
+}
+
+struct Sample {
+    index: usize,
+    weight: f32,
+    grad: f32x2, // gradient of the weight
+    pos: f32x2 // world position of the face
+}
+
+pub struct FlipSolver {
+    pub cell_size: f32,
+    pub x_cells: usize,
+    pub y_cells: usize,
+    pub transfer: FlipTransfer,
+    pub flip_ratio: f32, // 0 is pure PIC (damped, stable), 1 is pure FLIP (lively, noisy)
+    pub pressure_iterations: usize,
+    pub pressure_tolerance: f32,
+
+    pub cells: Vec<CellType>,
+    pub u: Vec<f32>,
+    pub v: Vec<f32>,
+    pub u_prev: Vec<f32>,
+    pub v_prev: Vec<f32>,
+    pub u_weight: Vec<f32>,
+    pub v_weight: Vec<f32>,
+    pub pressure: Vec<f32>,
+}
+
+impl FlipSolver {
+    // the grid covers the same world as the spatial hash, the outer ring of cells are walls
+    pub fn new(spatial_hash: &SpatialHash, cell_size: f32) -> FlipSolver {
+        let x_cells = (spatial_hash.x_size as f32 / cell_size).ceil() as usize;
+        let y_cells = (spatial_hash.y_size as f32 / cell_size).ceil() as usize;
+        let u_size = (x_cells + 1) * y_cells;
+        let v_size = x_cells * (y_cells + 1);
+        FlipSolver {
+            cell_size,
+            x_cells,
+            y_cells,
+            transfer: FlipTransfer::FlipPic,
+            flip_ratio: 0.95,
+            pressure_iterations: 200,
+            pressure_tolerance: 1e-4,
+            cells: vec![CellType::Air; x_cells * y_cells],
+            u: vec![0.0; u_size],
+            v: vec![0.0; v_size],
+            u_prev: vec![0.0; u_size],
+            v_prev: vec![0.0; v_size],
+            u_weight: vec![0.0; u_size],
+            v_weight: vec![0.0; v_size],
+            pressure: vec![0.0; x_cells * y_cells]
+        }
+    }
+
+    pub fn update(&mut self, particles: &mut Vec<Particle>, materials: &Vec<Material>, rects: &Vec<Rect>, force_fields: &Vec<ForceField>, properties: &Properties, dt: f32) {
+        // external forces other than gravity are applied directly to the particles
+        let dt2 = vec2_from_single(dt);
+        for particle in particles.iter_mut() {
+            let material = &materials[particle.material];
+            if !FlipSolver::is_flip_material(material) {
+                continue;
+            }
+
+            particle.vel += particle.buoyancy(properties, material) * dt2;
+            for force_field in force_fields.iter() {
+                particle.vel += force_field.force(particle) * dt2;
+            }
+        }
+
+        self.particles_to_grid(particles, materials);
+        self.mark_cells(particles, materials, rects);
+        self.extrapolate_velocity();
+        self.u_prev.copy_from_slice(&self.u);
+        self.v_prev.copy_from_slice(&self.v);
+
+        for u in self.u.iter_mut() {
+            *u += properties.gravity[0] * dt;
+        }
+        for v in self.v.iter_mut() {
+            *v += properties.gravity[1] * dt;
+        }
+
+        self.enforce_boundaries();
+        self.solve_pressure();
+        self.extrapolate_velocity();
+        self.grid_to_particles(particles, materials, rects, properties, dt);
+    }
+
+    // plastic materials are left to the mpm solver, solids are treated as obstacles
+    // gases are left to the particle pass so they keep their own gravity and drag
+    pub(crate) fn is_flip_material(material: &Material) -> bool {
+        return !material.uses_mpm() && !material.is_solid() && !material.is_gas();
+    }
+
+    #[inline(always)]
+    fn cell_index(&self, x: usize, y: usize) -> usize {
+        return x + y * self.x_cells;
+    }
+
+    // bilinear samples of a staggered field, offset is where the face sits within the cell
+    fn samples(&self, pos: f32x2, offset: f32x2, width: usize, height: usize) -> [Sample; 4] {
+        let g = pos / vec2_from_single(self.cell_size) - offset;
+        let x0 = (g[0].floor().max(0.0) as usize).min(width - 2);
+        let y0 = (g[1].floor().max(0.0) as usize).min(height - 2);
+        let fx = (g[0] - x0 as f32).max(0.0).min(1.0);
+        let fy = (g[1] - y0 as f32).max(0.0).min(1.0);
+        let inv_dx = 1.0 / self.cell_size;
+
+        let sample = |x: usize, y: usize, wx: f32, wy: f32, dwx: f32, dwy: f32| -> Sample {
+            Sample {
+                index: x + y * width,
+                weight: wx * wy,
+                grad: vec2(dwx * wy, wx * dwy) * vec2_from_single(inv_dx),
+                pos: (vec2(x as f32, y as f32) + offset) * vec2_from_single(self.cell_size)
+            }
+        };
+
+        return [
+            sample(x0, y0, 1.0 - fx, 1.0 - fy, -1.0, -1.0),
+            sample(x0 + 1, y0, fx, 1.0 - fy, 1.0, -1.0),
+            sample(x0, y0 + 1, 1.0 - fx, fy, -1.0, 1.0),
+            sample(x0 + 1, y0 + 1, fx, fy, 1.0, 1.0)
+        ];
+    }
+
+    fn u_samples(&self, pos: f32x2) -> [Sample; 4] {
+        return self.samples(pos, vec2(0.0, 0.5), self.x_cells + 1, self.y_cells);
+    }
+
+    fn v_samples(&self, pos: f32x2) -> [Sample; 4] {
+        return self.samples(pos, vec2(0.5, 0.0), self.x_cells, self.y_cells + 1);
+    }
+
+    fn particles_to_grid(&mut self, particles: &Vec<Particle>, materials: &Vec<Material>) {
+        self.u.iter_mut().for_each(|u| *u = 0.0);
+        self.v.iter_mut().for_each(|v| *v = 0.0);
+        self.u_weight.iter_mut().for_each(|w| *w = 0.0);
+        self.v_weight.iter_mut().for_each(|w| *w = 0.0);
+
+        let apic = self.transfer == FlipTransfer::Apic;
+        for particle in particles.iter() {
+            if !FlipSolver::is_flip_material(&materials[particle.material]) {
+                continue;
+            }
+
+            // the rows of the affine matrix are the gradients of u and v
+            let u_grad = if apic { vec2(particle.affine.xx, particle.affine.xy) } else { vec2_from_single(0.0) };
+            let v_grad = if apic { vec2(particle.affine.yx, particle.affine.yy) } else { vec2_from_single(0.0) };
+
+            for s in self.u_samples(particle.pos) {
+                self.u[s.index] += s.weight * (particle.vel[0] + dot(u_grad, s.pos - particle.pos));
+                self.u_weight[s.index] += s.weight;
+            }
+            for s in self.v_samples(particle.pos) {
+                self.v[s.index] += s.weight * (particle.vel[1] + dot(v_grad, s.pos - particle.pos));
+                self.v_weight[s.index] += s.weight;
+            }
+        }
+
+        for (u, w) in self.u.iter_mut().zip(self.u_weight.iter()) {
+            if *w > 0.0 {
+                *u /= *w;
+            }
+        }
+        for (v, w) in self.v.iter_mut().zip(self.v_weight.iter()) {
+            if *w > 0.0 {
+                *v /= *w;
+            }
+        }
+    }
+
+    fn mark_cells(&mut self, particles: &Vec<Particle>, materials: &Vec<Material>, rects: &Vec<Rect>) {
+        for y in 0..self.y_cells {
+            for x in 0..self.x_cells {
+                let index = self.cell_index(x, y);
+                let centre = vec2(x as f32 + 0.5, y as f32 + 0.5) * vec2_from_single(self.cell_size);
+                let border = x == 0 || y == 0 || x == self.x_cells - 1 || y == self.y_cells - 1;
+                self.cells[index] = if border || rects.iter().any(|rect| rect.contains(centre)) { CellType::Solid } else { CellType::Air };
+            }
+        }
+
+        for particle in particles.iter() {
+            // gases and mpm materials aren't transferred to the grid, so must not make cells incompressible
+            let material = &materials[particle.material];
+            if !FlipSolver::is_flip_material(material) && !material.is_solid() {
+                continue;
+            }
+
+            let x = ((particle.pos[0] / self.cell_size) as usize).min(self.x_cells - 1);
+            let y = ((particle.pos[1] / self.cell_size) as usize).min(self.y_cells - 1);
+            let index = self.cell_index(x, y);
+            if material.is_solid() {
+                self.cells[index] = CellType::Solid;
+            }
+            else if self.cells[index] == CellType::Air {
+                self.cells[index] = CellType::Fluid;
+            }
+        }
+    }
+
+    // solid walls don't move, so no velocity through any face touching a solid cell
+    fn enforce_boundaries(&mut self) {
+        for y in 0..self.y_cells {
+            for x in 0..self.x_cells {
+                if self.cells[self.cell_index(x, y)] != CellType::Solid {
+                    continue;
+                }
+                self.u[x + y * (self.x_cells + 1)] = 0.0;
+                self.u[x + 1 + y * (self.x_cells + 1)] = 0.0;
+                self.v[x + y * self.x_cells] = 0.0;
+                self.v[x + (y + 1) * self.x_cells] = 0.0;
+            }
+        }
+    }
+
+    // cell types either side of each face, None if the face is on the edge of the grid
+    fn u_face_cells(&self, x: usize, y: usize) -> Option<(CellType, CellType)> {
+        if x == 0 || x == self.x_cells {
+            return None;
+        }
+        return Some((self.cells[self.cell_index(x - 1, y)], self.cells[self.cell_index(x, y)]));
+    }
+
+    fn v_face_cells(&self, x: usize, y: usize) -> Option<(CellType, CellType)> {
+        if y == 0 || y == self.y_cells {
+            return None;
+        }
+        return Some((self.cells[self.cell_index(x, y - 1)], self.cells[self.cell_index(x, y)]));
+    }
+
+    fn is_fluid_face(cells: Option<(CellType, CellType)>) -> bool {
+        return match cells {
+            Some((a, b)) => a != CellType::Solid && b != CellType::Solid && (a == CellType::Fluid || b == CellType::Fluid),
+            None => false
+        };
+    }
+
+    // A * p, the 5 point laplacian over fluid cells. Air cells have zero pressure, solid cells are not counted
+    fn apply_laplacian(&self, p: &Vec<f32>, out: &mut Vec<f32>) {
+        for y in 0..self.y_cells {
+            for x in 0..self.x_cells {
+                let index = self.cell_index(x, y);
+                if self.cells[index] != CellType::Fluid {
+                    out[index] = 0.0;
+                    continue;
+                }
+
+                let mut diag = 0.0;
+                let mut sum = 0.0;
+                for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                    let n_index = self.cell_index(nx, ny);
+                    match self.cells[n_index] {
+                        CellType::Fluid => { diag += 1.0; sum += p[n_index]; },
+                        CellType::Air => { diag += 1.0; },
+                        CellType::Solid => {}
+                    }
+                }
+                out[index] = diag * p[index] - sum;
+            }
+        }
+    }
+
+    // number of non solid neighbours, the diagonal of the laplacian, used as a jacobi preconditioner
+    fn laplacian_diagonal(&self, x: usize, y: usize) -> f32 {
+        let mut diag = 0.0;
+        for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+            if self.cells[self.cell_index(nx, ny)] != CellType::Solid {
+                diag += 1.0;
+            }
+        }
+        return diag;
+    }
+
+    // solve A * p = -divergence with jacobi preconditioned conjugate gradient
+    // p is scaled by dt / (density * dx) so the velocity update is just the pressure difference
+    fn solve_pressure(&mut self) {
+        let size = self.x_cells * self.y_cells;
+        let mut residual = vec![0.0; size];
+        let mut inv_diag = vec![0.0; size];
+
+        // border cells are always solid, so neighbours of fluid cells are always in range
+        for y in 0..self.y_cells {
+            for x in 0..self.x_cells {
+                let index = self.cell_index(x, y);
+                if self.cells[index] != CellType::Fluid {
+                    continue;
+                }
+
+                let divergence = self.u[x + 1 + y * (self.x_cells + 1)] - self.u[x + y * (self.x_cells + 1)]
+                    + self.v[x + (y + 1) * self.x_cells] - self.v[x + y * self.x_cells];
+                residual[index] = -divergence;
+
+                let diag = self.laplacian_diagonal(x, y);
+                inv_diag[index] = if diag > 0.0 { 1.0 / diag } else { 0.0 };
+            }
+        }
+
+        let mut pressure = vec![0.0; size];
+        let mut z: Vec<f32> = residual.iter().zip(inv_diag.iter()).map(|(r, d)| r * d).collect();
+        let mut search = z.clone();
+        let mut a_search = vec![0.0; size];
+        let mut rz: f32 = residual.iter().zip(z.iter()).map(|(r, z)| r * z).sum();
+
+        for _i in 0..self.pressure_iterations {
+            let max_residual = residual.iter().fold(0.0 as f32, |m, r| m.max(r.abs()));
+            if max_residual < self.pressure_tolerance {
+                break;
+            }
+
+            self.apply_laplacian(&search, &mut a_search);
+            let search_a_search: f32 = search.iter().zip(a_search.iter()).map(|(s, a)| s * a).sum();
+            if search_a_search <= 0.0 {
+                break;
+            }
+
+            let alpha = rz / search_a_search;
+            for i in 0..size {
+                pressure[i] += alpha * search[i];
+                residual[i] -= alpha * a_search[i];
+                z[i] = residual[i] * inv_diag[i];
+            }
+
+            let rz_new: f32 = residual.iter().zip(z.iter()).map(|(r, z)| r * z).sum();
+            let beta = rz_new / rz;
+            rz = rz_new;
+            for i in 0..size {
+                search[i] = z[i] + beta * search[i];
+            }
+        }
+        self.pressure = pressure;
+
+        // subtract the pressure gradient
+        for y in 0..self.y_cells {
+            for x in 0..(self.x_cells + 1) {
+                if FlipSolver::is_fluid_face(self.u_face_cells(x, y)) {
+                    self.u[x + y * (self.x_cells + 1)] -= self.pressure[self.cell_index(x, y)] - self.pressure[self.cell_index(x - 1, y)];
+                }
+            }
+        }
+        for y in 0..(self.y_cells + 1) {
+            for x in 0..self.x_cells {
+                if FlipSolver::is_fluid_face(self.v_face_cells(x, y)) {
+                    self.v[x + y * self.x_cells] -= self.pressure[self.cell_index(x, y)] - self.pressure[self.cell_index(x, y - 1)];
+                }
+            }
+        }
+    }
+
+    // copy velocities from faces next to fluid into the surrounding air, so particles near the surface
+    // sample a sensible velocity
+    fn extrapolate_velocity(&mut self) {
+        const LAYERS: usize = 2;
+
+        let u_valid: Vec<bool> = (0..self.u.len()).map(|i| FlipSolver::is_fluid_face(self.u_face_cells(i % (self.x_cells + 1), i / (self.x_cells + 1)))).collect();
+        let v_valid: Vec<bool> = (0..self.v.len()).map(|i| FlipSolver::is_fluid_face(self.v_face_cells(i % self.x_cells, i / self.x_cells))).collect();
+        FlipSolver::extrapolate_field(&mut self.u, u_valid, self.x_cells + 1, self.y_cells, LAYERS);
+        FlipSolver::extrapolate_field(&mut self.v, v_valid, self.x_cells, self.y_cells + 1, LAYERS);
+        self.enforce_boundaries();
+    }
+
+    fn extrapolate_field(field: &mut Vec<f32>, mut valid: Vec<bool>, width: usize, height: usize, layers: usize) {
+        for _layer in 0..layers {
+            let mut next_valid = valid.clone();
+            for y in 0..height {
+                for x in 0..width {
+                    let index = x + y * width;
+                    if valid[index] {
+                        continue;
+                    }
+
+                    let mut sum = 0.0;
+                    let mut count = 0;
+                    if x > 0 && valid[index - 1] { sum += field[index - 1]; count += 1; }
+                    if x + 1 < width && valid[index + 1] { sum += field[index + 1]; count += 1; }
+                    if y > 0 && valid[index - width] { sum += field[index - width]; count += 1; }
+                    if y + 1 < height && valid[index + width] { sum += field[index + width]; count += 1; }
+
+                    if count > 0 {
+                        field[index] = sum / count as f32;
+                        next_valid[index] = true;
+                    }
+                }
+            }
+            valid = next_valid;
+        }
+    }
+
+    fn grid_to_particles(&mut self, particles: &mut Vec<Particle>, materials: &Vec<Material>, rects: &Vec<Rect>, properties: &Properties, dt: f32) {
+        let min_pos = vec2_from_single(self.cell_size * 1.001);
+        let max_pos = vec2(self.x_cells as f32 - 1.001, self.y_cells as f32 - 1.001) * vec2_from_single(self.cell_size);
+
+        for particle in particles.iter_mut() {
+            if !FlipSolver::is_flip_material(&materials[particle.material]) {
+                continue;
+            }
+
+            let mut pic = vec2_from_single(0.0);
+            let mut delta = vec2_from_single(0.0);
+            let mut u_grad = vec2_from_single(0.0);
+            let mut v_grad = vec2_from_single(0.0);
+
+            for s in self.u_samples(particle.pos) {
+                pic[0] += s.weight * self.u[s.index];
+                delta[0] += s.weight * (self.u[s.index] - self.u_prev[s.index]);
+                u_grad += s.grad * vec2_from_single(self.u[s.index]);
+            }
+            for s in self.v_samples(particle.pos) {
+                pic[1] += s.weight * self.v[s.index];
+                delta[1] += s.weight * (self.v[s.index] - self.v_prev[s.index]);
+                v_grad += s.grad * vec2_from_single(self.v[s.index]);
+            }
+
+            match self.transfer {
+                FlipTransfer::FlipPic => {
+                    let flip = particle.vel + delta;
+                    particle.vel = flip * vec2_from_single(self.flip_ratio) + pic * vec2_from_single(1.0 - self.flip_ratio);
+                    particle.affine = Mat2::zero();
+                },
+                FlipTransfer::Apic => {
+                    particle.vel = pic;
+                    particle.affine = Mat2::new(u_grad[0], u_grad[1], v_grad[0], v_grad[1]);
+                }
+            }
+
+            particle.pos += particle.vel * vec2_from_single(dt);
+
+            // TODO: spatial hashing
+            for rect in rects.iter() {
+                rect.collide_with(particle, properties);
+            }
+
+            particle.pos = particle.pos.clamp(min_pos, max_pos);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::flip::{FlipSolver, CellType};
+    use crate::material::Material;
+    use crate::particle::Particle;
+    use crate::spatial_hash::SpatialHash;
+    use crate::vector_2::*;
+
+    #[test]
+    fn gas_does_not_mark_fluid_cells() {
+        let mut flip = FlipSolver::new(&SpatialHash::new(10, 10), 1.0);
+        let materials = vec![Material::new("water"), Material::gas("smoke")];
+        let water = Particle::new(vec2(3.5, 3.5));
+        let mut smoke = Particle::new(vec2(6.5, 6.5));
+        smoke.material = 1;
+
+        flip.mark_cells(&vec![water, smoke], &materials, &vec![]);
+
+        assert_eq!(flip.cells[flip.cell_index(3, 3)], CellType::Fluid);
+        assert_eq!(flip.cells[flip.cell_index(6, 6)], CellType::Air);
+    }
+}
\ No newline at end of file
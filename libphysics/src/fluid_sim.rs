@@ -1,59 +1,130 @@
+use std::collections::VecDeque;
+use std::path::Path;
 use core_simd::*;
 use rand::distributions::{Distribution, Uniform};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Serialize, Deserialize};
 
 use crate::rect::Rect;
 use crate::shape::Shape;
 use crate::spatial_hash::SpatialHash;
 use crate::spatial_hash_iter::SpatialHashIter;
-use crate::particle::Particle;
+use crate::particle::{Particle, ParticleId};
+use crate::sensor::{Sensor, SensorId, SensorEvent};
+use crate::material::{Material, MaterialId};
+use crate::emitter::Emitter;
+use crate::sink::Sink;
+use crate::lattice::Lattice;
+use crate::circle::Circle;
+use crate::force_field::ForceField;
+use crate::mpm::MpmSolver;
+use crate::flip::FlipSolver;
+use crate::cluster::{Cluster, ClusterId};
+use crate::reaction::{Reaction, ReactionTable};
+use crate::snapshot::{Snapshot, SensorState};
+use crate::scene::Scene;
+use crate::exporter::ExportFormat;
+use crate::marching_squares::{Surface, SurfaceOptions};
 use crate::vector_2::*;
 
+// any fields missing from a scene file are given the defaults
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Properties {
     pub collision_energy_loss: f32, // when colliding, energy loss on velocity
     pub elasticity: f32, // when intersecting what to multiply velocity by. Lower means particles can squish together more
-    pub damping: f32, // energy loss. Higher means velocity becomes more like viscous - honey. Lower more like water
+    pub damping: f32, // fraction of velocity kept each second, 1.0 for no energy loss. For honey like fluids use Material::viscosity instead
     pub collision_damping: f32, // energy loss during collisions
+    pub surface_tension: f32, // cohesion between neighbouring particles, 0 to disable. Higher makes small volumes bead up into droplets
+    #[serde(with = "serde_vec2")]
     pub gravity: f32x2,
+    pub ambient_temperature: f32, // particles at this temperature are neutrally buoyant
+    #[serde(with = "serde_vec2")]
+    pub wind: f32x2, // velocity of the surrounding air, materials with drag are pulled towards it
     pub radius: f32,
-    pub dist_squared_max: f32,
+    #[serde(skip)]
+    pub dist_squared_max: f32, // derived from the radius
+}
+
+impl Default for Properties {
+    fn default() -> Properties {
+        let radius: f32 = 1.0;
+        Properties {
+            collision_energy_loss: 1.0,
+            elasticity: 1.0,
+            damping: 1.0,
+            collision_damping: 1.0,
+            surface_tension: 0.0,
+            radius,
+            dist_squared_max: (radius + radius) * (radius + radius),
+            gravity: Simd::from_array([0.0, 0.3]),
+            ambient_temperature: 20.0,
+            wind: vec2_from_single(0.0)
+        }
+    }
 }
 
 pub struct FluidSim {
     pub spatial_hash: SpatialHash,
     pub particles: Vec<Particle>,
     pub properties: Properties,
+    pub materials: Vec<Material>,
     //pub shapes: Vec<Box<dyn Shape>>,
     pub rects: Vec<Rect>,
+    pub sensors: Vec<Sensor>,
+    pub sensor_events: VecDeque<SensorEvent>,
+    pub emitters: Vec<Emitter>,
+    pub sinks: Vec<Sink>,
+    pub force_fields: Vec<ForceField>,
+    pub mpm: Option<MpmSolver>, // created on demand when a plastic material is added
+    pub flip: Option<FlipSolver>, // when set, liquid particles are moved by the grid instead of particle contacts
+    pub clusters: Vec<Cluster>,
+    pub reactions: ReactionTable,
+    pub next_particle_id: ParticleId,
+    pub rng: StdRng, // all randomness in the sim comes from here so a seeded sim is reproducible
 }
 
 impl FluidSim {
     pub fn new(x_size: usize, y_size: usize) -> FluidSim {
-        let radius: f32 = 1.0;
         FluidSim {
             spatial_hash: SpatialHash::new(x_size, y_size),
-            properties: Properties {
-                collision_energy_loss: 1.0,
-                elasticity: 1.0,
-                damping: 1.0,
-                collision_damping: 1.0,
-                radius,
-                dist_squared_max: (radius + radius) * (radius + radius),
-                gravity: Simd::from_array([0.0, 0.3]),
-            },
+            properties: Properties::default(),
             particles: vec![],
+            materials: vec![Material::new("water")],
             //shapes: vec![],
-            rects: vec![]
+            rects: vec![],
+            sensors: vec![],
+            sensor_events: VecDeque::new(),
+            emitters: vec![],
+            sinks: vec![],
+            force_fields: vec![],
+            mpm: None,
+            flip: None,
+            clusters: vec![],
+            reactions: ReactionTable::new(),
+            next_particle_id: 0,
+            rng: StdRng::from_entropy()
         }
     }
 
-    pub fn generate_random_particles(&self, count: usize) -> Vec<Particle> {
+    pub fn with_seed(x_size: usize, y_size: usize, seed: u64) -> FluidSim {
+        let mut fluid_sim = FluidSim::new(x_size, y_size);
+        fluid_sim.set_seed(seed);
+        return fluid_sim;
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn generate_random_particles(&mut self, count: usize) -> Vec<Particle> {
         let range = Uniform::from(0.0..1.0);
-        let mut rng = rand::thread_rng();
         let mut particles: Vec<Particle> = Vec::new();
 
         for _b in 0..count {
-            let pt_x = range.sample(&mut rng) * (self.spatial_hash.x_size as f32);
-            let pt_y = range.sample(&mut rng) * (self.spatial_hash.y_size as f32);
+            let pt_x = range.sample(&mut self.rng) * (self.spatial_hash.x_size as f32);
+            let pt_y = range.sample(&mut self.rng) * (self.spatial_hash.y_size as f32);
             particles.push(Particle::new(Simd::from_array([pt_x, pt_y])));
             //println!("pt-{:?}: {:?},{:?}", pts.len() / 2, pt_x, pt_y);
         }
@@ -61,11 +132,280 @@ impl FluidSim {
         return particles;
     }
 
+    // fill a shape with particles at rest spacing (touching, but not overlapping)
+    // skips any points that are inside existing colliders or outside the world
+    pub fn generate_particles_in_shape(&self, shape: &dyn Shape, lattice: Lattice) -> Vec<Particle> {
+        let spacing = self.properties.radius * 2.0;
+        let (min, max) = shape.aabb();
+        let mut particles: Vec<Particle> = Vec::new();
+
+        for pt in lattice.points(min, max, spacing) {
+            if !shape.contains(pt) || !self.is_inside_bounds(pt) {
+                continue;
+            }
+
+            if self.rects.iter().any(|rect| rect.contains(pt)) {
+                continue;
+            }
+
+            particles.push(Particle::new(pt));
+        }
+
+        return particles;
+    }
+
     pub fn add_particles(&mut self, particles: &Vec<Particle>) {
         for particle in particles {
-            self.particles.push(particle.clone());
+            self.insert_particle(particle.clone());
+        }
+        self.spatial_hash_particles();
+    }
+
+    // add a particle without rehashing, the caller is responsible for calling spatial_hash_particles
+    fn insert_particle(&mut self, mut particle: Particle) -> ParticleId {
+        let id = self.next_particle_id;
+        particle.id = id;
+        if particle.temperature.is_nan() {
+            particle.temperature = self.properties.ambient_temperature;
+        }
+        self.next_particle_id += 1;
+        self.particles.push(particle);
+        return id;
+    }
+
+    pub fn add_reaction(&mut self, reaction: Reaction) {
+        self.reactions.add(reaction);
+    }
+
+    // particles are always kept sorted by id
+    pub fn particle_index(&self, id: ParticleId) -> Option<usize> {
+        return self.particles.binary_search_by_key(&id, |particle| particle.id).ok();
+    }
+
+    // add particles that are held together by shape matching, their current positions are the rest shape
+    pub fn add_cluster(&mut self, particles: &Vec<Particle>, stiffness: f32) -> Result<ClusterId, String> {
+        // a single particle has no shape to match
+        if particles.len() < 2 {
+            return Err(format!("a cluster needs at least 2 particles, got {}", particles.len()));
+        }
+
+        let cluster_id = self.clusters.len();
+        for particle in particles {
+            let mut particle = particle.clone();
+            particle.cluster = Some(cluster_id);
+            self.insert_particle(particle);
+        }
+        self.spatial_hash_particles();
+
+        // the new particles are at the end of the list and now have their ids
+        let start = self.particles.len() - particles.len();
+        self.clusters.push(Cluster::new(&self.particles[start..], stiffness));
+        return Ok(cluster_id);
+    }
+
+    pub fn is_inside_bounds(&self, pos: f32x2) -> bool {
+        return pos[0] >= 0.0 && pos[1] >= 0.0 && (pos[0] as usize) < self.spatial_hash.x_size && (pos[1] as usize) < self.spatial_hash.y_size;
+    }
+
+    // index of the closest particle to pos within max_dist, for picking with the mouse
+    // TODO: spatial hashing
+    pub fn particle_at(&self, pos: f32x2, max_dist: f32) -> Option<usize> {
+        let mut closest: Option<(usize, f32)> = None;
+        for (i, particle) in self.particles.iter().enumerate() {
+            let dist_squared = length_squared(particle.pos - pos);
+            if dist_squared <= max_dist * max_dist && closest.map_or(true, |(_, closest_dist)| dist_squared < closest_dist) {
+                closest = Some((i, dist_squared));
+            }
+        }
+        return closest.map(|(i, _)| i);
+    }
+
+    // fill a circle with particles of a material, leaving out any that would overlap existing particles
+    // returns how many were added, for painting with a brush
+    pub fn spawn_particles_in_circle(&mut self, pos: f32x2, radius: f32, material: MaterialId) -> usize {
+        let spacing = self.properties.radius * 2.0;
+        let mut particles = self.generate_particles_in_shape(&Circle { pos, radius }, Lattice::Hex);
+        particles.retain(|particle| self.particle_at(particle.pos, spacing * 0.9).is_none());
+        for particle in particles.iter_mut() {
+            particle.material = material;
+        }
+
+        if !particles.is_empty() {
+            self.add_particles(&particles);
+            self.clear_contacts();
+        }
+        return particles.len();
+    }
+
+    // returns how many were removed, for erasing with a brush
+    pub fn remove_particles_in_circle(&mut self, pos: f32x2, radius: f32) -> usize {
+        let count = self.particles.len();
+        self.particles.retain(|particle| length_squared(particle.pos - pos) > radius * radius);
+        let removed = count - self.particles.len();
+        if removed > 0 {
+            self.clear_contacts();
+            self.spatial_hash_particles();
+        }
+        return removed;
+    }
+
+    // contacts point into the particle list, so must be cleared when particles are added or removed outside of update
+    fn clear_contacts(&mut self) {
+        for particle in self.particles.iter_mut() {
+            particle.contacts.clear();
+        }
+    }
+
+    // index of the rect containing pos, the last added is on top
+    pub fn rect_at(&self, pos: f32x2) -> Option<usize> {
+        return self.rects.iter().rposition(|rect| rect.contains(pos));
+    }
+
+    // spawn particles from emitters and remove those in sinks
+    // returns true if the particle list changed and so needs rehashing
+    fn update_emitters_and_sinks(&mut self, dt: f32) -> bool {
+        let mut changed = false;
+
+        for i in 0..self.emitters.len() {
+            let particles = self.emitters[i].emit(dt, &mut self.rng);
+            for particle in particles {
+                // anything emitted outside the world would not fit in the spatial hash
+                if self.is_inside_bounds(particle.pos) {
+                    self.insert_particle(particle);
+                    changed = true;
+                }
+            }
+        }
+
+        for sink in self.sinks.iter_mut() {
+            if sink.remove_particles(&mut self.particles) {
+                changed = true;
+            }
+        }
+
+        return changed;
+    }
+
+    // age all particles and remove those that have faded out
+    fn update_lifetimes(&mut self, dt: f32) -> bool {
+        for particle in self.particles.iter_mut() {
+            particle.age += dt;
+        }
+
+        let materials = &self.materials;
+        let count = self.particles.len();
+        self.particles.retain(|particle| match materials[particle.material].lifetime {
+            Some(lifetime) => particle.age < lifetime,
+            None => true
+        });
+        return self.particles.len() != count;
+    }
+
+    pub fn add_material(&mut self, material: Material) -> MaterialId {
+        self.materials.push(material);
+        self.create_mpm_solver_if_needed();
+        return self.materials.len() - 1;
+    }
+
+    // plastic materials need a background grid, the grid cells are the rest spacing of the particles
+    // also checked each update as materials can be pushed directly
+    fn create_mpm_solver_if_needed(&mut self) {
+        if self.mpm.is_none() && self.materials.iter().any(|material| material.uses_mpm()) {
+            self.mpm = Some(MpmSolver::new(&self.spatial_hash, self.properties.radius * 2.0));
+        }
+    }
+
+    pub fn add_emitter(&mut self, emitter: Emitter) -> usize {
+        self.emitters.push(emitter);
+        return self.emitters.len() - 1;
+    }
+
+    pub fn add_sink(&mut self, shape: Box<dyn Shape>) -> usize {
+        self.sinks.push(Sink::new(shape));
+        return self.sinks.len() - 1;
+    }
+
+    pub fn add_force_field(&mut self, force_field: ForceField) -> usize {
+        self.force_fields.push(force_field);
+        return self.force_fields.len() - 1;
+    }
+
+    pub fn add_sensor(&mut self, shape: Box<dyn Shape>) -> SensorId {
+        self.sensors.push(Sensor::new(shape));
+        return self.sensors.len() - 1;
+    }
+
+    // events accumulate until they are polled, so drain them every frame
+    pub fn poll_sensor_event(&mut self) -> Option<SensorEvent> {
+        return self.sensor_events.pop_front();
+    }
+
+    // the format is picked from the file extension: ron, json or toml
+    pub fn load_scene(path: &Path) -> Result<FluidSim, String> {
+        return Scene::load(path)?.to_fluid_sim();
+    }
+
+    pub fn save_scene(&self, path: &Path) -> Result<(), String> {
+        return Scene::from_fluid_sim(self).save(path);
+    }
+
+    // write the particles for analysis in other tools, the format is picked from the file extension: vtk, ply or csv
+    pub fn export_frame(&self, path: &Path) -> Result<(), String> {
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        let format = ExportFormat::from_extension(extension).ok_or(format!("unknown export format '{}', expected vtk, ply or csv", extension))?;
+        return format.write_file(self, path);
+    }
+
+    // the outline of the fluid as polylines and triangles
+    pub fn surface(&self, options: &SurfaceOptions) -> Surface {
+        return Surface::extract(self, options);
+    }
+
+    // capture the state of the sim, restoring it and stepping again gives bit identical results
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            particles: self.particles.iter().map(|particle| particle.without_contacts()).collect(),
+            rects: self.rects.clone(),
+            properties: self.properties.clone(),
+            emitters: self.emitters.clone(),
+            sensors: self.sensors.iter().map(|sensor| SensorState { inside: sensor.inside.clone(), count: sensor.count, volume: sensor.volume }).collect(),
+            sensor_events: self.sensor_events.clone(),
+            sinks_removed: self.sinks.iter().map(|sink| sink.removed).collect(),
+            clusters: self.clusters.clone(),
+            next_particle_id: self.next_particle_id,
+            rng: self.rng.clone()
+        }
+    }
+
+    // the sensors and sinks must be the same ones that existed when the snapshot was taken
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), String> {
+        if snapshot.sensors.len() != self.sensors.len() {
+            return Err(format!("snapshot has {} sensors but the sim has {}", snapshot.sensors.len(), self.sensors.len()));
+        }
+        if snapshot.sinks_removed.len() != self.sinks.len() {
+            return Err(format!("snapshot has {} sinks but the sim has {}", snapshot.sinks_removed.len(), self.sinks.len()));
         }
+
+        self.particles = snapshot.particles.iter().map(|particle| particle.without_contacts()).collect();
+        self.rects = snapshot.rects.clone();
+        self.properties = snapshot.properties.clone();
+        self.emitters = snapshot.emitters.clone();
+        for (sensor, state) in self.sensors.iter_mut().zip(snapshot.sensors.iter()) {
+            sensor.inside = state.inside.clone();
+            sensor.count = state.count;
+            sensor.volume = state.volume;
+        }
+        self.sensor_events = snapshot.sensor_events.clone();
+        for (sink, removed) in self.sinks.iter_mut().zip(snapshot.sinks_removed.iter()) {
+            sink.removed = *removed;
+        }
+        self.clusters = snapshot.clusters.clone();
+        self.next_particle_id = snapshot.next_particle_id;
+        self.rng = snapshot.rng.clone();
+
+        // the hash holds pointers to the old particles
         self.spatial_hash_particles();
+        return Ok(());
     }
 
     pub fn spatial_hash_particles(&mut self) {
@@ -73,9 +413,7 @@ impl FluidSim {
         self.spatial_hash.add_particles(&mut self.particles);
     }
 
-    pub fn update(&mut self, dt: f32) {
-        let dt2: f32x2 = vec2_from_single(dt);
-
+    fn update_particle_contacts(&mut self, dt2: f32x2) {
         // compute particle contacts
         let mut cell_it = SpatialHashIter::new(&self.spatial_hash);
         while cell_it.next() {
@@ -84,28 +422,303 @@ impl FluidSim {
                 let particle = *particle_it;
     
                 unsafe {
-                    (*particle).check_particle_collisions(&cell_it, &self.properties, dt2);
+                    // particles moved by the grid don't need contacts, but everything else still collides, reacts and shares heat
+                    // they are left with no density so their neighbours skip them for normals and heat flow
+                    if self.flip.is_some() && FlipSolver::is_flip_material(&self.materials[(*particle).material]) {
+                        (*particle).contacts.clear();
+                        (*particle).density = 0.0;
+                        continue;
+                    }
+
+                    (*particle).check_particle_collisions(&cell_it, &self.properties, &self.materials, dt2);
                 }
             }
         }
     
-        self.spatial_hash.clear();
-    
+        // surface normals
+        for particle in self.particles.iter_mut() {
+            particle.compute_normal(&self.properties);
+        }
+    }
+
+    // check each particle's contacts against the reaction table
+    // a particle can only take part in one reaction per step
+    fn update_reactions(&mut self, dt: f32) {
+        if self.reactions.is_empty() {
+            return;
+        }
+
+        let base = self.particles.as_mut_ptr();
+        let mut reacted = vec![false; self.particles.len()];
+        for i in 0..self.particles.len() {
+            if reacted[i] {
+                continue;
+            }
+
+            let material = self.particles[i].material;
+            for c in 0..self.particles[i].contacts.len() {
+                // contacts point into the particle list, so we can get back the index
+                let j = unsafe { self.particles[i].contacts[c].particle.offset_from(base) as usize };
+                if reacted[j] {
+                    continue;
+                }
+
+                let reaction = match self.reactions.get(material, self.particles[j].material) {
+                    Some(reaction) => *reaction,
+                    None => continue
+                };
+
+                if self.rng.gen::<f32>() >= reaction.probability * dt {
+                    continue;
+                }
+
+                self.particles[i].material = reaction.products.0;
+                self.particles[i].temperature += reaction.energy;
+                self.particles[j].material = reaction.products.1;
+                self.particles[j].temperature += reaction.energy;
+                reacted[i] = true;
+                reacted[j] = true;
+                break;
+            }
+        }
+    }
+
+    // heat diffusion between particles and shapes, then any phase changes
+    fn update_temperature(&mut self, dt: f32) {
+        let flows: Vec<f32> = self.particles.iter().map(|particle| particle.heat_flow(&self.properties, &self.materials, dt)).collect();
+
+        for (particle, flow) in self.particles.iter_mut().zip(flows) {
+            particle.temperature += flow;
+
+            // TODO: spatial hashing
+            let conductivity = self.materials[particle.material].conductivity;
+            for rect in self.rects.iter() {
+                if let Some(temperature) = rect.temperature {
+                    let (dist, _normal) = rect.signed_distance(particle.pos);
+                    if dist < self.properties.radius {
+                        particle.temperature += (conductivity * dt).min(1.0) * (temperature - particle.temperature);
+                    }
+                }
+            }
+
+            let material = &self.materials[particle.material];
+            if let Some(phase) = material.phase_above {
+                if particle.temperature > phase.temperature {
+                    particle.material = phase.material;
+                    continue;
+                }
+            }
+            if let Some(phase) = material.phase_below {
+                if particle.temperature < phase.temperature {
+                    particle.material = phase.material;
+                }
+            }
+        }
+    }
+
+    fn update_clusters(&mut self, dt: f32) {
+        let max_pos = vec2(self.spatial_hash.x_size as f32, self.spatial_hash.y_size as f32) - vec2_from_single(0.001);
+        for cluster in self.clusters.iter_mut() {
+            if cluster.is_empty() {
+                continue;
+            }
+
+            let broken = cluster.update(&mut self.particles, &self.properties, dt);
+            for id in broken {
+                if let Ok(index) = self.particles.binary_search_by_key(&id, |particle| particle.id) {
+                    self.particles[index].cluster = None;
+                }
+            }
+        }
+
+        // shape matching can pull particles outside of the world
+        if !self.clusters.is_empty() {
+            for particle in self.particles.iter_mut() {
+                if particle.cluster.is_some() {
+                    particle.pos = particle.pos.clamp(vec2_from_single(0.0), max_pos);
+                }
+            }
+        }
+    }
+
+    fn move_particles(&mut self, dt2: f32x2) {
         // propogate & update velocity
         for particle in self.particles.iter_mut() {
-            particle.update_velocity(&self.spatial_hash, &self.properties, dt2);
+            if self.materials[particle.material].uses_mpm() {
+                continue;
+            }
+
+            // already moved by the grid
+            if self.flip.is_some() && FlipSolver::is_flip_material(&self.materials[particle.material]) {
+                continue;
+            }
+
+            // solids stay where they are, but still push other particles away
+            if self.materials[particle.material].is_solid() && particle.cluster.is_none() {
+                particle.vel = vec2_from_single(0.0);
+                continue;
+            }
+
+            particle.update_velocity(&self.spatial_hash, &self.properties, &self.materials, &self.force_fields, dt2);
         }
 
         // we move the particles
         for particle in self.particles.iter_mut() {
+            let material = &self.materials[particle.material];
+            if material.uses_mpm() || (material.is_solid() && particle.cluster.is_none()) {
+                continue;
+            }
+
+            if self.flip.is_some() && FlipSolver::is_flip_material(material) {
+                continue;
+            }
+
             // collision detection with any rects (TODO: spatial hashing)
             for rect in self.rects.iter() {
                 rect.collide_with(particle, &self.properties);
             }
 
             particle.move_pos(&self.spatial_hash, &self.properties, dt2);
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        let dt2: f32x2 = vec2_from_single(dt);
 
-            self.spatial_hash.add_particle(particle);
+        self.create_mpm_solver_if_needed();
+
+        // adding or removing particles invalidates the particle pointers in the spatial hash
+        let lifetimes_changed = self.update_lifetimes(dt);
+        if self.update_emitters_and_sinks(dt) || lifetimes_changed {
+            self.spatial_hash_particles();
         }
+
+        self.update_particle_contacts(dt2);
+
+        self.update_reactions(dt);
+        self.update_temperature(dt);
+
+        self.spatial_hash.clear();
+
+        if let Some(flip) = &mut self.flip {
+            flip.update(&mut self.particles, &self.materials, &self.rects, &self.force_fields, &self.properties, dt);
+        }
+
+        // anything the grid solver doesn't handle
+        self.move_particles(dt2);
+
+        // plastic materials are moved by the mpm grid
+        if let Some(mpm) = &mut self.mpm {
+            mpm.update(&mut self.particles, &self.materials, &self.rects, &self.properties, dt);
+        }
+
+        self.update_clusters(dt);
+
+        self.spatial_hash.add_particles(&mut self.particles);
+
+        // sensors don't collide, they just report what is inside them
+        for (i, sensor) in self.sensors.iter_mut().enumerate() {
+            sensor.update(i, &self.particles, &self.properties, &mut self.sensor_events);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fluid_sim::FluidSim;
+    use crate::flip::FlipSolver;
+    use crate::material::{Material, MatterState};
+    use crate::particle::Particle;
+    use crate::vector_2::*;
+
+    #[test]
+    fn plastic_material_pushed_directly_still_moves() {
+        let mut fluid_sim = FluidSim::with_seed(20, 20, 0);
+        fluid_sim.materials.push(Material::sand());
+        let mut particle = Particle::new(vec2(10.0, 5.0));
+        particle.material = 1;
+        fluid_sim.add_particles(&vec![particle]);
+
+        for _ in 0..10 {
+            fluid_sim.update(0.05);
+        }
+
+        assert!(fluid_sim.mpm.is_some());
+        assert!(fluid_sim.particles[0].pos[1] > 5.0);
+    }
+
+    #[test]
+    fn flip_still_gives_gases_and_clusters_contacts() {
+        let mut fluid_sim = FluidSim::with_seed(40, 20, 0);
+        fluid_sim.flip = Some(FlipSolver::new(&fluid_sim.spatial_hash, 1.0));
+        let smoke = fluid_sim.add_material(Material::gas("smoke"));
+        let mut rock = Material::new("rock");
+        rock.state = MatterState::Solid;
+        let rock = fluid_sim.add_material(rock);
+
+        let with_material = |pos: f32x2, material: usize| {
+            let mut particle = Particle::new(pos);
+            particle.material = material;
+            return particle;
+        };
+        fluid_sim.add_particles(&vec![
+            Particle::new(vec2(5.0, 10.0)),
+            Particle::new(vec2(5.8, 10.0)),
+            with_material(vec2(15.0, 10.0), smoke),
+            with_material(vec2(15.8, 10.0), smoke),
+            with_material(vec2(25.4, 10.8), rock)
+        ]);
+        fluid_sim.add_cluster(&vec![with_material(vec2(25.0, 10.0), rock), with_material(vec2(25.8, 10.0), rock)], 1.0).unwrap();
+
+        fluid_sim.update(0.01);
+
+        let contacts = |x: f32| fluid_sim.particles.iter().find(|p| (p.pos[0] - x).abs() < 0.2 && p.pos[1] < 10.5).unwrap().contacts.len();
+        // water is moved by the grid so has none
+        assert_eq!(contacts(5.0), 0);
+        assert_eq!(contacts(5.8), 0);
+        assert_eq!(contacts(15.0), 1);
+        assert_eq!(contacts(15.8), 1);
+        assert_eq!(contacts(25.0), 2);
+        assert_eq!(contacts(25.8), 2);
+
+        // the gas pushes itself apart
+        let smoke_particles: Vec<&Particle> = fluid_sim.particles.iter().filter(|p| p.material == smoke).collect();
+        assert!(smoke_particles[0].vel[0] < 0.0 && smoke_particles[1].vel[0] > 0.0);
+    }
+
+    #[test]
+    fn added_particles_start_at_the_ambient_temperature() {
+        let mut fluid_sim = FluidSim::with_seed(20, 20, 0);
+        fluid_sim.properties.ambient_temperature = 50.0;
+        let mut hot = Particle::new(vec2(15.0, 5.0));
+        hot.temperature = 80.0;
+        fluid_sim.add_particles(&vec![Particle::new(vec2(5.0, 5.0)), hot]);
+        fluid_sim.add_cluster(&vec![Particle::new(vec2(10.0, 5.0)), Particle::new(vec2(10.8, 5.0))], 1.0).unwrap();
+
+        assert_eq!(fluid_sim.particles[0].temperature, 50.0);
+        assert_eq!(fluid_sim.particles[1].temperature, 80.0);
+        assert_eq!(fluid_sim.particles[2].temperature, 50.0);
+        assert_eq!(fluid_sim.particles[3].temperature, 50.0);
+    }
+
+    #[test]
+    fn clusters_need_two_particles() {
+        let mut fluid_sim = FluidSim::with_seed(20, 20, 0);
+        assert!(fluid_sim.add_cluster(&vec![Particle::new(vec2(5.0, 5.0))], 1.0).is_err());
+        assert!(fluid_sim.particles.is_empty());
+        assert!(fluid_sim.clusters.is_empty());
+    }
+
+    #[test]
+    fn cluster_left_with_one_particle_releases_it() {
+        let mut fluid_sim = FluidSim::with_seed(20, 20, 0);
+        let cluster = fluid_sim.add_cluster(&vec![Particle::new(vec2(5.0, 5.0)), Particle::new(vec2(15.0, 5.0))], 1.0).unwrap();
+        fluid_sim.remove_particles_in_circle(vec2(15.0, 5.0), 1.0);
+        fluid_sim.update(0.01);
+
+        assert!(fluid_sim.clusters[cluster].particles.is_empty());
+        assert!(fluid_sim.clusters[cluster].rest_positions.is_empty());
+        assert_eq!(fluid_sim.particles.len(), 1);
+        assert_eq!(fluid_sim.particles[0].cluster, None);
     }
 }
\ No newline at end of file
@@ -0,0 +1,63 @@
+use core_simd::*;
+use crate::particle::Particle;
+use crate::rect::Rect;
+use crate::shape::Shape;
+use crate::vector_2::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Falloff {
+    Constant,
+    Linear, // full strength at the centre down to zero at the radius
+    InverseSquare,
+}
+
+impl Falloff {
+    pub fn scale(&self, dist: f32, radius: f32) -> f32 {
+        return match self {
+            Falloff::Constant => 1.0,
+            Falloff::Linear => 1.0 - (dist / radius),
+            Falloff::InverseSquare => 1.0 / (1.0 + dist * dist),
+        };
+    }
+}
+
+// external forces applied to particles on top of gravity, for things like fans and whirlpools
+// forces are accelerations, the same as gravity
+pub enum ForceField {
+    Wind { region: Rect, force: f32x2 }, // constant force inside the region
+    Vortex { pos: f32x2, radius: f32, strength: f32 }, // positive strength spins counterclockwise, linear falloff
+    Radial { pos: f32x2, radius: f32, strength: f32, falloff: Falloff }, // positive strength repels, negative attracts
+    Custom(Box<dyn Fn(&Particle) -> f32x2>),
+}
+
+impl ForceField {
+    pub fn force(&self, particle: &Particle) -> f32x2 {
+        match self {
+            ForceField::Wind { region, force } => {
+                if region.contains(particle.pos) {
+                    return *force;
+                }
+            },
+            ForceField::Vortex { pos, radius, strength } => {
+                let delta = particle.pos - *pos;
+                let dist = length_squared(delta).sqrt();
+                if dist > 0.0 && dist < *radius {
+                    let tangent = vec2(-delta[1], delta[0]) / vec2_from_single(dist);
+                    return tangent * vec2_from_single(strength * Falloff::Linear.scale(dist, *radius));
+                }
+            },
+            ForceField::Radial { pos, radius, strength, falloff } => {
+                let delta = particle.pos - *pos;
+                let dist = length_squared(delta).sqrt();
+                if dist > 0.0 && dist < *radius {
+                    let normal = delta / vec2_from_single(dist);
+                    return normal * vec2_from_single(strength * falloff.scale(dist, *radius));
+                }
+            },
+            ForceField::Custom(func) => {
+                return func(particle);
+            }
+        }
+        return vec2_from_single(0.0);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,48 @@
+// SPH smoothing kernels, h is the support radius
+// https://matthias-research.github.io/pages/publications/sca03.pdf
+use std::f32::consts::PI;
+
+// 2d poly6, used for density
+#[inline(always)]
+pub fn poly6(r: f32, h: f32) -> f32 {
+    if r >= h {
+        return 0.0;
+    }
+    let d = h * h - r * r;
+    return 4.0 / (PI * h.powi(8)) * d * d * d;
+}
+
+// magnitude of the 2d spiky kernel gradient, points away from the neighbour
+#[inline(always)]
+pub fn spiky_gradient(r: f32, h: f32) -> f32 {
+    if r >= h {
+        return 0.0;
+    }
+    let d = h - r;
+    return -30.0 / (PI * h.powi(5)) * d * d;
+}
+
+// cohesion spline from Akinci et al. 2013 "Versatile Surface Tension and Adhesion for SPH Fluids"
+// attracts at long range and repels when particles get too close
+// https://cg.informatik.uni-freiburg.de/publications/2013_SIGGRAPHASIA_surfaceTensionAdhesion.pdf
+#[inline(always)]
+pub fn cohesion(r: f32, h: f32) -> f32 {
+    if r <= 0.0 || r > h {
+        return 0.0;
+    }
+    let c = 32.0 / (PI * h.powi(9));
+    let d = h - r;
+    if 2.0 * r > h {
+        return c * d * d * d * r * r * r;
+    }
+    return c * (2.0 * d * d * d * r * r * r - h.powi(6) / 64.0);
+}
+
+// laplacian of the 2d viscosity kernel
+#[inline(always)]
+pub fn viscosity_laplacian(r: f32, h: f32) -> f32 {
+    if r >= h {
+        return 0.0;
+    }
+    return 40.0 / (PI * h.powi(5)) * (h - r);
+}
\ No newline at end of file
@@ -0,0 +1,39 @@
+use core_simd::*;
+use serde::{Serialize, Deserialize};
+use crate::vector_2::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Lattice {
+    Square,
+    Hex, // every other row is offset by half the spacing, packs tighter
+}
+
+impl Lattice {
+    // all the lattice points within the box from min to max
+    pub fn points(&self, min: f32x2, max: f32x2, spacing: f32) -> Vec<f32x2> {
+        let mut points: Vec<f32x2> = Vec::new();
+        if spacing <= 0.0 {
+            return points;
+        }
+
+        let row_spacing = match self {
+            Lattice::Square => spacing,
+            Lattice::Hex => spacing * (3.0 as f32).sqrt() * 0.5,
+        };
+
+        let mut row = 0;
+        let mut y = min[1] + spacing * 0.5;
+        while y <= max[1] {
+            let row_offset = if *self == Lattice::Hex && row % 2 == 1 { spacing * 0.5 } else { 0.0 };
+            let mut x = min[0] + spacing * 0.5 + row_offset;
+            while x <= max[0] {
+                points.push(vec2(x, y));
+                x += spacing;
+            }
+            y += row_spacing;
+            row += 1;
+        }
+
+        return points;
+    }
+}
\ No newline at end of file
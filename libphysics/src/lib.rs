@@ -6,18 +6,56 @@ pub use core_simd::*;
 pub use crate::fluid_sim::FluidSim;
 pub use crate::spatial_hash::SpatialHash;
 pub use crate::spatial_hash_iter::SpatialHashIter;
-pub use crate::particle::Particle;
+pub use crate::particle::{Particle, ParticleId};
 pub use crate::test::*;
 pub use crate::shape::Shape;
 pub use crate::rect::Rect;
+pub use crate::circle::Circle;
+pub use crate::polygon::Polygon;
+pub use crate::lattice::Lattice;
+pub use crate::force_field::{ForceField, Falloff};
+pub use crate::sensor::{Sensor, SensorId, SensorEvent};
+pub use crate::material::{Material, MaterialId, ViscosityModel, Plasticity, MatterState, PhaseChange};
+pub use crate::matrix_2::Mat2;
+pub use crate::mpm::{MpmSolver, MpmNode};
+pub use crate::flip::{FlipSolver, FlipTransfer, CellType};
+pub use crate::cluster::{Cluster, ClusterId};
+pub use crate::reaction::{Reaction, ReactionTable};
+pub use crate::snapshot::{Snapshot, SensorState};
+pub use crate::scene::{Scene, SceneShape, SceneParticle, ParticleBlock};
+pub use crate::particle_cache::{CacheOptions, CacheFrame, ParticleCacheWriter, ParticleCacheReader};
+pub use crate::exporter::ExportFormat;
+pub use crate::marching_squares::{Surface, SurfaceOptions, DensityField};
+pub use crate::emitter::{Emitter, EmitterShape};
+pub use crate::sink::Sink;
 pub use crate::vector_2::*;
 
 mod spatial_hash;
 mod spatial_hash_iter;
 mod particle;
 mod test;
+#[cfg(test)]
 mod bench;
 mod fluid_sim;
 mod shape;
 mod rect;
+mod circle;
+mod polygon;
+mod lattice;
+mod force_field;
+mod kernel;
+mod matrix_2;
+mod mpm;
+mod flip;
+mod cluster;
+mod reaction;
+mod sensor;
+mod material;
+mod snapshot;
+mod scene;
+mod particle_cache;
+mod exporter;
+mod marching_squares;
+mod emitter;
+mod sink;
 mod vector_2;
\ No newline at end of file
@@ -0,0 +1,515 @@
+use core_simd::*;
+use crate::fluid_sim::FluidSim;
+use crate::vector_2::*;
+
+// extracts the outline of the fluid as geometry for drawing filled liquid, buoyancy and line of sight checks
+// each particle adds a linear falloff to a density field sampled on a grid, the surface is where it crosses the threshold
+// http://jamie-wong.com/2014/08/19/metaballs-and-marching-squares/
+//
+// corners and edges of a cell, y is down:
+//
+//   c0 --e0-- c1
+//   |          |
+//   e3        e1
+//   |          |
+//   c3 --e2-- c2
+
+pub struct SurfaceOptions {
+    pub cell_size: f32, // grid spacing in world units, smaller is smoother but slower
+    pub radius: f32, // falloff radius in particle radii
+    pub threshold: f32, // density of the surface, a lone particle has a density of 1 at its centre
+    pub include_gases: bool,
+}
+
+impl SurfaceOptions {
+    pub fn new() -> SurfaceOptions {
+        SurfaceOptions {
+            cell_size: 0.5,
+            radius: 2.5,
+            threshold: 0.6,
+            include_gases: false
+        }
+    }
+}
+
+// density sampled at the corners of the grid cells
+// the grid has an extra cell of padding around the world that is always empty, so every outline is closed
+pub struct DensityField {
+    pub origin: f32x2,
+    pub cell_size: f32,
+    pub x_samples: usize,
+    pub y_samples: usize,
+    pub values: Vec<f32>,
+}
+
+impl DensityField {
+    pub fn from_fluid_sim(fluid_sim: &FluidSim, options: &SurfaceOptions) -> DensityField {
+        let cell_size = options.cell_size.max(0.01);
+        let x_samples = (fluid_sim.spatial_hash.x_size as f32 / cell_size).ceil() as usize + 3;
+        let y_samples = (fluid_sim.spatial_hash.y_size as f32 / cell_size).ceil() as usize + 3;
+        let mut field = DensityField {
+            origin: vec2(-cell_size, -cell_size),
+            cell_size,
+            x_samples,
+            y_samples,
+            values: vec![0.0; x_samples * y_samples]
+        };
+
+        let radius = options.radius * fluid_sim.properties.radius;
+        if radius <= 0.0 {
+            return field;
+        }
+
+        // TODO: spatial hashing, neighbouring particles touch the same samples
+        for particle in fluid_sim.particles.iter() {
+            if !options.include_gases && fluid_sim.materials[particle.material].is_gas() {
+                continue;
+            }
+
+            let local = (particle.pos - field.origin) / vec2_from_single(cell_size);
+            let reach = radius / cell_size;
+            let min_x = (local[0] - reach).ceil().max(1.0) as usize;
+            let min_y = (local[1] - reach).ceil().max(1.0) as usize;
+            let max_x = ((local[0] + reach).floor().max(0.0) as usize).min(x_samples - 2);
+            let max_y = ((local[1] + reach).floor().max(0.0) as usize).min(y_samples - 2);
+            for y in min_y..=max_y {
+                for x in min_x..=max_x {
+                    let dist = length_squared(field.position(x, y) - particle.pos).sqrt();
+                    if dist < radius {
+                        field.values[x + y * x_samples] += 1.0 - dist / radius;
+                    }
+                }
+            }
+        }
+
+        return field;
+    }
+
+    pub fn position(&self, x: usize, y: usize) -> f32x2 {
+        return self.origin + vec2(x as f32, y as f32) * vec2_from_single(self.cell_size);
+    }
+
+    pub fn value(&self, x: usize, y: usize) -> f32 {
+        return self.values[x + y * self.x_samples];
+    }
+
+    // bilinear interpolation of the samples, 0 outside the grid
+    pub fn sample(&self, pos: f32x2) -> f32 {
+        let local = (pos - self.origin) / vec2_from_single(self.cell_size);
+        if local[0] < 0.0 || local[1] < 0.0 {
+            return 0.0;
+        }
+
+        let x = local[0].floor() as usize;
+        let y = local[1].floor() as usize;
+        if x + 1 >= self.x_samples || y + 1 >= self.y_samples {
+            return 0.0;
+        }
+
+        let tx = local[0] - x as f32;
+        let ty = local[1] - y as f32;
+        let top = self.value(x, y) * (1.0 - tx) + self.value(x + 1, y) * tx;
+        let bottom = self.value(x, y + 1) * (1.0 - tx) + self.value(x + 1, y + 1) * tx;
+        return top * (1.0 - ty) + bottom * ty;
+    }
+}
+
+// the fluid outline as closed polylines plus a triangulation of the inside
+pub struct Surface {
+    pub polylines: Vec<Vec<f32x2>>, // closed loops, the last point joins back to the first
+    pub triangles: Vec<[f32x2; 3]>,
+}
+
+// the pairs of edges each case crosses, the saddle cases 5 and 10 depend on the centre of the cell
+const SEGMENTS: [&[(usize, usize)]; 16] = [
+    &[],
+    &[(3, 0)],
+    &[(0, 1)],
+    &[(3, 1)],
+    &[(1, 2)],
+    &[(3, 0), (1, 2)], // centre outside
+    &[(0, 2)],
+    &[(3, 2)],
+    &[(3, 2)],
+    &[(0, 2)],
+    &[(0, 1), (3, 2)], // centre outside
+    &[(1, 2)],
+    &[(3, 1)],
+    &[(0, 1)],
+    &[(3, 0)],
+    &[]
+];
+
+const SADDLE_5_JOINED: &[(usize, usize)] = &[(0, 1), (3, 2)];
+const SADDLE_10_JOINED: &[(usize, usize)] = &[(3, 0), (1, 2)];
+
+impl Surface {
+    pub fn extract(fluid_sim: &FluidSim, options: &SurfaceOptions) -> Surface {
+        let field = DensityField::from_fluid_sim(fluid_sim, options);
+        return Surface::from_field(&field, options.threshold);
+    }
+
+    pub fn from_field(field: &DensityField, threshold: f32) -> Surface {
+        let mut surface = Surface {
+            polylines: vec![],
+            triangles: vec![]
+        };
+
+        // segments are joined into polylines through the grid edge they cross
+        // horizontal edges are numbered first, then vertical edges
+        let horizontal_edges = (field.x_samples - 1) * field.y_samples;
+        let mut segments: Vec<(usize, usize)> = vec![];
+        let mut edge_points: Vec<f32x2> = vec![vec2(0.0, 0.0); horizontal_edges + field.x_samples * (field.y_samples - 1)];
+
+        for y in 0..field.y_samples - 1 {
+            for x in 0..field.x_samples - 1 {
+                let corners = [(x, y), (x + 1, y), (x + 1, y + 1), (x, y + 1)];
+                let values = corners.map(|(cx, cy)| field.value(cx, cy));
+                let positions = corners.map(|(cx, cy)| field.position(cx, cy));
+
+                let mut case = 0;
+                for i in 0..4 {
+                    if values[i] >= threshold {
+                        case |= 1 << i;
+                    }
+                }
+                if case == 0 {
+                    continue;
+                }
+
+                let edges = [
+                    x + y * (field.x_samples - 1),
+                    horizontal_edges + (x + 1) + y * field.x_samples,
+                    x + (y + 1) * (field.x_samples - 1),
+                    horizontal_edges + x + y * field.x_samples
+                ];
+
+                // where the surface crosses each edge
+                let mut crossings = [vec2(0.0, 0.0); 4];
+                for i in 0..4 {
+                    let (a, b) = if i == 3 { (0, 3) } else if i == 2 { (3, 2) } else { (i, i + 1) };
+                    let inside_a = values[a] >= threshold;
+                    let inside_b = values[b] >= threshold;
+                    if inside_a != inside_b {
+                        let t = (threshold - values[a]) / (values[b] - values[a]);
+                        crossings[i] = positions[a] + (positions[b] - positions[a]) * vec2_from_single(t);
+                        edge_points[edges[i]] = crossings[i];
+                    }
+                }
+
+                let centre_inside = values.iter().sum::<f32>() * 0.25 >= threshold;
+                let saddle = case == 5 || case == 10;
+                let cell_segments = if case == 5 && centre_inside {
+                    SADDLE_5_JOINED
+                }
+                else if case == 10 && centre_inside {
+                    SADDLE_10_JOINED
+                }
+                else {
+                    SEGMENTS[case]
+                };
+
+                for (a, b) in cell_segments.iter() {
+                    segments.push((edges[*a], edges[*b]));
+                }
+
+                // fill the inside of the cell, walking around it collecting inside corners and crossings
+                if saddle && !centre_inside {
+                    for i in [0, 2].iter().map(|i| if case == 5 { *i } else { *i + 1 }) {
+                        let previous_edge = (i + 3) % 4;
+                        surface.triangles.push([positions[i], crossings[i], crossings[previous_edge]]);
+                    }
+                    continue;
+                }
+
+                let mut polygon: Vec<f32x2> = vec![];
+                for i in 0..4 {
+                    if case & (1 << i) != 0 {
+                        polygon.push(positions[i]);
+                    }
+                    let next = (i + 1) % 4;
+                    if (case & (1 << i) != 0) != (case & (1 << next) != 0) {
+                        polygon.push(crossings[i]);
+                    }
+                }
+
+                if saddle {
+                    // the joined saddle isn't convex so fan from the centre of the cell
+                    let centre = (positions[0] + positions[2]) * vec2_from_single(0.5);
+                    for i in 0..polygon.len() {
+                        surface.triangles.push([centre, polygon[i], polygon[(i + 1) % polygon.len()]]);
+                    }
+                }
+                else {
+                    for i in 1..polygon.len() - 1 {
+                        surface.triangles.push([polygon[0], polygon[i], polygon[i + 1]]);
+                    }
+                }
+            }
+        }
+
+        surface.polylines = Surface::join_segments(&segments, &edge_points);
+        return surface;
+    }
+
+    // each edge point is shared by exactly two segments as the outlines are closed
+    fn join_segments(segments: &Vec<(usize, usize)>, edge_points: &Vec<f32x2>) -> Vec<Vec<f32x2>> {
+        let mut by_edge: Vec<[usize; 2]> = vec![[usize::MAX; 2]; edge_points.len()];
+        for (i, (a, b)) in segments.iter().enumerate() {
+            for edge in [*a, *b] {
+                let slot = if by_edge[edge][0] == usize::MAX { 0 } else { 1 };
+                by_edge[edge][slot] = i;
+            }
+        }
+
+        let mut polylines: Vec<Vec<f32x2>> = vec![];
+        let mut used = vec![false; segments.len()];
+        for start in 0..segments.len() {
+            if used[start] {
+                continue;
+            }
+
+            let mut polyline: Vec<f32x2> = vec![];
+            let mut segment = start;
+            let mut edge = segments[start].0;
+            while !used[segment] {
+                used[segment] = true;
+                polyline.push(edge_points[edge]);
+
+                edge = if segments[segment].0 == edge { segments[segment].1 } else { segments[segment].0 };
+                let [a, b] = by_edge[edge];
+                segment = if a == segment { b } else { a };
+                if segment == usize::MAX {
+                    break;
+                }
+            }
+            polylines.push(polyline);
+        }
+        return polylines;
+    }
+
+    pub fn area(&self) -> f32 {
+        return self.triangles.iter().map(|t| triangle_area(t)).sum();
+    }
+
+    pub fn contains(&self, point: f32x2) -> bool {
+        return self.triangles.iter().any(|t| triangle_contains(t, point));
+    }
+
+    // the first point along a to b where it crosses the outline, None if there is a clear line of sight
+    pub fn intersect_segment(&self, a: f32x2, b: f32x2) -> Option<f32x2> {
+        let mut closest: Option<f32> = None;
+        for polyline in self.polylines.iter() {
+            for i in 0..polyline.len() {
+                let c = polyline[i];
+                let d = polyline[(i + 1) % polyline.len()];
+                if let Some(t) = segment_intersection(a, b, c, d) {
+                    if closest.map_or(true, |closest| t < closest) {
+                        closest = Some(t);
+                    }
+                }
+            }
+        }
+        return closest.map(|t| a + (b - a) * vec2_from_single(t));
+    }
+}
+
+fn cross(a: f32x2, b: f32x2) -> f32 {
+    return a[0] * b[1] - a[1] * b[0];
+}
+
+fn triangle_area(t: &[f32x2; 3]) -> f32 {
+    return cross(t[1] - t[0], t[2] - t[0]).abs() * 0.5;
+}
+
+// a crossing can land on a corner, leaving a triangle with no area that would otherwise contain everything in line with it
+fn triangle_contains(t: &[f32x2; 3], p: f32x2) -> bool {
+    if cross(t[1] - t[0], t[2] - t[0]) == 0.0 {
+        return false;
+    }
+
+    let d0 = cross(t[1] - t[0], p - t[0]);
+    let d1 = cross(t[2] - t[1], p - t[1]);
+    let d2 = cross(t[0] - t[2], p - t[2]);
+    let has_negative = d0 < 0.0 || d1 < 0.0 || d2 < 0.0;
+    let has_positive = d0 > 0.0 || d1 > 0.0 || d2 > 0.0;
+    return !(has_negative && has_positive);
+}
+
+// how far along a to b it crosses c to d, from 0 to 1
+fn segment_intersection(a: f32x2, b: f32x2, c: f32x2, d: f32x2) -> Option<f32> {
+    let r = b - a;
+    let s = d - c;
+    let denominator = cross(r, s);
+    if denominator.abs() < 1e-9 {
+        return None;
+    }
+
+    let t = cross(c - a, s) / denominator;
+    let u = cross(c - a, r) / denominator;
+    if t >= 0.0 && t <= 1.0 && u >= 0.0 && u <= 1.0 {
+        return Some(t);
+    }
+    return None;
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use core_simd::*;
+    use crate::marching_squares::{DensityField, Surface};
+    use crate::vector_2::*;
+
+    const THRESHOLD: f32 = 0.6;
+
+    fn field(x_samples: usize, y_samples: usize, value: impl Fn(usize, usize) -> f32) -> DensityField {
+        let mut values = vec![0.0; x_samples * y_samples];
+        for y in 0..y_samples {
+            for x in 0..x_samples {
+                values[x + y * x_samples] = value(x, y);
+            }
+        }
+        return DensityField { origin: vec2(0.0, 0.0), cell_size: 1.0, x_samples, y_samples, values };
+    }
+
+    // a cone that crosses the threshold at the given radius
+    fn blob(centre: f32x2, radius: f32) -> DensityField {
+        return field(21, 21, |x, y| {
+            let dist = length_squared(vec2(x as f32, y as f32) - centre).sqrt();
+            return (1.0 - (1.0 - THRESHOLD) * dist / radius).max(0.0);
+        });
+    }
+
+    // the 2x2 corners of the middle cell of a 4x4 field with an empty border
+    fn saddle(c0: f32, c1: f32, c2: f32, c3: f32) -> DensityField {
+        return field(4, 4, |x, y| match (x, y) {
+            (1, 1) => c0,
+            (2, 1) => c1,
+            (2, 2) => c2,
+            (1, 2) => c3,
+            _ => 0.0
+        });
+    }
+
+    // https://en.wikipedia.org/wiki/Shoelace_formula
+    fn polyline_area(polyline: &Vec<f32x2>) -> f32 {
+        let mut area = 0.0;
+        for i in 0..polyline.len() {
+            let a = polyline[i];
+            let b = polyline[(i + 1) % polyline.len()];
+            area += a[0] * b[1] - a[1] * b[0];
+        }
+        return (area * 0.5).abs();
+    }
+
+    fn inside_polyline(polyline: &Vec<f32x2>, p: f32x2) -> bool {
+        let mut inside = false;
+        for i in 0..polyline.len() {
+            let a = polyline[i];
+            let b = polyline[(i + 1) % polyline.len()];
+            if (a[1] > p[1]) != (b[1] > p[1]) && p[0] < a[0] + (p[1] - a[1]) / (b[1] - a[1]) * (b[0] - a[0]) {
+                inside = !inside;
+            }
+        }
+        return inside;
+    }
+
+    // holes are inside an odd number of other outlines and take away from the area
+    fn assert_triangles_match_polylines(surface: &Surface) {
+        let outline_area: f32 = surface.polylines.iter().enumerate().map(|(i, polyline)| {
+            let depth = surface.polylines.iter().enumerate().filter(|(j, other)| *j != i && inside_polyline(other, polyline[0])).count();
+            return if depth % 2 == 0 { polyline_area(polyline) } else { -polyline_area(polyline) };
+        }).sum();
+        assert!((surface.area() - outline_area).abs() < 1e-4 * outline_area.max(1.0), "triangles cover {} but the outlines enclose {}", surface.area(), outline_area);
+    }
+
+    #[test]
+    fn single_blob_is_one_closed_outline() {
+        let surface = Surface::from_field(&blob(vec2(10.0, 10.0), 5.0), THRESHOLD);
+
+        assert_eq!(surface.polylines.len(), 1);
+        let polyline = &surface.polylines[0];
+        assert!(polyline.len() > 8);
+        for point in polyline.iter() {
+            let dist = length_squared(*point - vec2(10.0, 10.0)).sqrt();
+            assert!((dist - 5.0).abs() < 0.25, "outline point {:?} is {} from the centre", point, dist);
+        }
+
+        let expected = std::f32::consts::PI * 25.0;
+        assert!((surface.area() - expected).abs() < expected * 0.03, "area {} expected {}", surface.area(), expected);
+        assert_triangles_match_polylines(&surface);
+        assert!(surface.contains(vec2(10.0, 10.0)));
+        assert!(!surface.contains(vec2(10.0, 16.0)));
+    }
+
+    #[test]
+    fn saddle_with_centre_outside_stays_split() {
+        for field in [saddle(1.0, 0.0, 1.0, 0.0), saddle(0.0, 1.0, 0.0, 1.0)] {
+            let surface = Surface::from_field(&field, THRESHOLD);
+            assert_eq!(surface.polylines.len(), 2);
+            assert!(!surface.contains(vec2(1.5, 1.5)));
+            assert_triangles_match_polylines(&surface);
+        }
+    }
+
+    #[test]
+    fn saddle_with_centre_inside_is_joined() {
+        for field in [saddle(1.0, 0.5, 1.0, 0.5), saddle(0.5, 1.0, 0.5, 1.0)] {
+            let surface = Surface::from_field(&field, THRESHOLD);
+            assert_eq!(surface.polylines.len(), 1);
+            assert!(surface.contains(vec2(1.5, 1.5)));
+            assert_triangles_match_polylines(&surface);
+        }
+    }
+
+    #[test]
+    fn intersect_segment_finds_the_first_crossing() {
+        let surface = Surface::from_field(&blob(vec2(10.0, 10.0), 5.0), THRESHOLD);
+
+        let hit = surface.intersect_segment(vec2(1.0, 10.0), vec2(19.0, 10.0)).unwrap();
+        assert!((hit[0] - 5.0).abs() < 0.1 && (hit[1] - 10.0).abs() < 1e-4, "hit at {:?}", hit);
+
+        let hit = surface.intersect_segment(vec2(10.0, 10.0), vec2(10.0, 19.0)).unwrap();
+        assert!((hit[0] - 10.0).abs() < 1e-4 && (hit[1] - 15.0).abs() < 0.1, "hit at {:?}", hit);
+
+        assert_eq!(surface.intersect_segment(vec2(1.0, 1.0), vec2(19.0, 1.0)), None);
+        assert_eq!(surface.intersect_segment(vec2(9.0, 10.0), vec2(11.0, 10.0)), None);
+    }
+
+    // join_segments relies on every crossed edge being shared by exactly two segments,
+    // then there is one outline point per crossed edge and every outline closes on itself
+    #[test]
+    fn every_crossed_edge_joins_two_segments() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _i in 0..20 {
+            let values: Vec<f32> = (0..30 * 30).map(|_| rng.gen::<f32>()).collect();
+            let field = field(30, 30, |x, y| if x == 0 || y == 0 || x == 29 || y == 29 { 0.0 } else { values[x + y * 30] });
+
+            let inside = |x: usize, y: usize| field.value(x, y) >= THRESHOLD;
+            let mut crossed_edges = 0;
+            for y in 0..30 {
+                for x in 0..30 {
+                    if x + 1 < 30 && inside(x, y) != inside(x + 1, y) {
+                        crossed_edges += 1;
+                    }
+                    if y + 1 < 30 && inside(x, y) != inside(x, y + 1) {
+                        crossed_edges += 1;
+                    }
+                }
+            }
+
+            let surface = Surface::from_field(&field, THRESHOLD);
+            assert_eq!(surface.polylines.iter().map(|polyline| polyline.len()).sum::<usize>(), crossed_edges);
+            for polyline in surface.polylines.iter() {
+                assert!(polyline.len() >= 3);
+                for i in 0..polyline.len() {
+                    // neighbouring points, including the last and first, are crossings of the same cell
+                    let step = polyline[(i + 1) % polyline.len()] - polyline[i];
+                    assert!(step[0].abs() <= 1.0 && step[1].abs() <= 1.0, "outline jumps by {:?}", step);
+                }
+            }
+            assert_triangles_match_polylines(&surface);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,146 @@
+use serde::{Serialize, Deserialize};
+
+pub type MaterialId = usize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ViscosityModel {
+    Newtonian, // constant viscosity, water, honey
+    PowerLaw { consistency: f32, flow_index: f32 }, // flow_index < 1 is shear thinning (paint), > 1 is shear thickening (oobleck)
+    Bingham { yield_stress: f32, plastic_viscosity: f32 }, // barely flows until the shear stress passes the yield stress (mud)
+}
+
+impl ViscosityModel {
+    // the apparent viscosity at the given shear rate
+    pub fn effective_viscosity(&self, viscosity: f32, shear_rate: f32) -> f32 {
+        const MIN_SHEAR_RATE: f32 = 0.001; // keeps power law and bingham finite when at rest
+        const MAX_VISCOSITY: f32 = 1000.0;
+
+        let shear_rate = shear_rate.max(MIN_SHEAR_RATE);
+        let effective = match self {
+            ViscosityModel::Newtonian => viscosity,
+            ViscosityModel::PowerLaw { consistency, flow_index } => consistency * shear_rate.powf(flow_index - 1.0),
+            ViscosityModel::Bingham { yield_stress, plastic_viscosity } => plastic_viscosity + yield_stress / shear_rate,
+        };
+        return effective.max(0.0).min(MAX_VISCOSITY);
+    }
+}
+
+// plastic materials are simulated by the MpmSolver instead of particle contacts
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Plasticity {
+    DruckerPrager { friction_angle: f32 }, // sand, friction angle in degrees. Klar et al. 2016
+    Snow { critical_compression: f32, critical_stretch: f32, hardening: f32 }, // Stomakhin et al. 2013
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MatterState {
+    Solid, // stays where it is unless part of a cluster
+    Liquid,
+    Gas
+}
+
+// switch a particle to another material when it crosses a temperature
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PhaseChange {
+    pub temperature: f32,
+    pub material: MaterialId
+}
+
+// material 0 is always the default material (water) that particles are created with
+// any fields missing from a scene file are given the defaults from Material::new
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Material {
+    pub name: String,
+    pub viscosity: f32, // laplacian viscosity used by the Newtonian model, 0 for an inviscid fluid
+    pub viscosity_model: ViscosityModel,
+    pub xsph: f32, // 0 to 1, blends each particle's velocity towards its neighbours which smooths out noisy motion
+    pub youngs_modulus: f32, // stiffness of plastic materials
+    pub poissons_ratio: f32, // 0 to 0.5, how much plastic materials bulge when squashed
+    pub plasticity: Option<Plasticity>,
+    pub state: MatterState,
+    pub conductivity: f32, // how quickly heat moves to and from neighbours, 0 to not take part in heat transfer
+    pub expansion: f32, // buoyancy per degree above the ambient temperature, hot particles rise
+    pub phase_above: Option<PhaseChange>, // melting or boiling
+    pub phase_below: Option<PhaseChange>, // freezing or condensing
+    pub gravity_scale: f32, // multiplier on the sim gravity, negative makes a gas rise
+    pub repulsion: f32, // multiplier on the push from neighbouring particles, gases only weakly push each other apart
+    pub drag: f32, // how quickly the velocity is pulled towards the ambient wind, per second
+    pub lifetime: Option<f32>, // seconds before the particle has faded out and is removed
+}
+
+impl Material {
+    pub fn new(name: &str) -> Material {
+        Material {
+            name: name.to_string(),
+            viscosity: 0.0,
+            viscosity_model: ViscosityModel::Newtonian,
+            xsph: 0.0,
+            youngs_modulus: 1.0e4,
+            poissons_ratio: 0.3,
+            plasticity: None,
+            state: MatterState::Liquid,
+            conductivity: 0.0,
+            expansion: 0.0,
+            phase_above: None,
+            phase_below: None,
+            gravity_scale: 1.0,
+            repulsion: 1.0,
+            drag: 0.0,
+            lifetime: None
+        }
+    }
+
+    pub fn sand() -> Material {
+        let mut material = Material::new("sand");
+        material.youngs_modulus = 3.5e4;
+        material.plasticity = Some(Plasticity::DruckerPrager { friction_angle: 30.0 });
+        return material;
+    }
+
+    pub fn snow() -> Material {
+        let mut material = Material::new("snow");
+        material.youngs_modulus = 1.4e4;
+        material.poissons_ratio = 0.2;
+        material.plasticity = Some(Plasticity::Snow { critical_compression: 2.5e-2, critical_stretch: 7.5e-3, hardening: 10.0 });
+        return material;
+    }
+
+    // smoke or steam, floats up and drifts with the wind until it fades away
+    pub fn gas(name: &str) -> Material {
+        let mut material = Material::new(name);
+        material.state = MatterState::Gas;
+        material.gravity_scale = -0.3;
+        material.repulsion = 0.1;
+        material.drag = 1.0;
+        material.lifetime = Some(5.0);
+        return material;
+    }
+
+    pub fn is_solid(&self) -> bool {
+        return self.state == MatterState::Solid;
+    }
+
+    pub fn is_gas(&self) -> bool {
+        return self.state == MatterState::Gas;
+    }
+
+    pub fn uses_mpm(&self) -> bool {
+        return self.plasticity.is_some();
+    }
+
+    // lame parameters (mu, lambda) from the youngs modulus and poissons ratio
+    pub fn lame_parameters(&self) -> (f32, f32) {
+        let e = self.youngs_modulus;
+        let nu = self.poissons_ratio;
+        let mu = e / (2.0 * (1.0 + nu));
+        let lambda = e * nu / ((1.0 + nu) * (1.0 - 2.0 * nu));
+        return (mu, lambda);
+    }
+}
+
+impl Default for Material {
+    fn default() -> Material {
+        Material::new("")
+    }
+}
\ No newline at end of file
@@ -0,0 +1,129 @@
+use std::ops::{Add, Sub, Mul};
+use core_simd::*;
+use crate::vector_2::*;
+
+// 2x2 matrix, row major:
+// | xx xy |
+// | yx yy |
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat2 {
+    pub xx: f32,
+    pub xy: f32,
+    pub yx: f32,
+    pub yy: f32
+}
+
+impl Mat2 {
+    pub fn new(xx: f32, xy: f32, yx: f32, yy: f32) -> Mat2 {
+        Mat2 { xx, xy, yx, yy }
+    }
+
+    pub fn identity() -> Mat2 {
+        Mat2::new(1.0, 0.0, 0.0, 1.0)
+    }
+
+    pub fn zero() -> Mat2 {
+        Mat2::new(0.0, 0.0, 0.0, 0.0)
+    }
+
+    pub fn diagonal(d: f32x2) -> Mat2 {
+        Mat2::new(d[0], 0.0, 0.0, d[1])
+    }
+
+    pub fn rotation(angle_rad: f32) -> Mat2 {
+        let c = angle_rad.cos();
+        let s = angle_rad.sin();
+        Mat2::new(c, -s, s, c)
+    }
+
+    // a * b^T
+    pub fn outer(a: f32x2, b: f32x2) -> Mat2 {
+        Mat2::new(a[0] * b[0], a[0] * b[1], a[1] * b[0], a[1] * b[1])
+    }
+
+    pub fn transpose(&self) -> Mat2 {
+        Mat2::new(self.xx, self.yx, self.xy, self.yy)
+    }
+
+    pub fn determinant(&self) -> f32 {
+        return self.xx * self.yy - self.xy * self.yx;
+    }
+
+    pub fn trace(&self) -> f32 {
+        return self.xx + self.yy;
+    }
+
+    // None if the matrix is singular
+    pub fn inverse(&self) -> Option<Mat2> {
+        let det = self.determinant();
+        if det.abs() <= f32::EPSILON {
+            return None;
+        }
+        return Some(Mat2::new(self.yy, -self.xy, -self.yx, self.xx).scale(1.0 / det));
+    }
+
+    pub fn scale(&self, s: f32) -> Mat2 {
+        Mat2::new(self.xx * s, self.xy * s, self.yx * s, self.yy * s)
+    }
+
+    pub fn mul_vec(&self, v: f32x2) -> f32x2 {
+        return vec2(self.xx * v[0] + self.xy * v[1], self.yx * v[0] + self.yy * v[1]);
+    }
+
+    // closest rotation to this matrix, the R of the polar decomposition M = RS
+    pub fn polar_rotation(&self) -> Mat2 {
+        let angle = (self.yx - self.xy).atan2(self.xx + self.yy);
+        return Mat2::rotation(angle);
+    }
+
+    // singular value decomposition M = U * diag(sigma) * V^T
+    // done as a polar decomposition followed by an eigen decomposition of the symmetric part
+    // singular values can be negative if the matrix is inverted
+    pub fn svd(&self) -> (Mat2, f32x2, Mat2) {
+        let r = self.polar_rotation();
+        let s = r.transpose() * *self;
+
+        // eigen decomposition of the symmetric matrix s
+        let angle = 0.5 * (s.xy + s.yx).atan2(s.xx - s.yy);
+        let c = angle.cos();
+        let sn = angle.sin();
+        let b = 0.5 * (s.xy + s.yx);
+        let sigma = vec2(
+            s.xx * c * c + 2.0 * b * c * sn + s.yy * sn * sn,
+            s.xx * sn * sn - 2.0 * b * c * sn + s.yy * c * c
+        );
+
+        let v = Mat2::rotation(angle);
+        let u = r * v;
+        return (u, sigma, v);
+    }
+}
+
+impl Add for Mat2 {
+    type Output = Mat2;
+
+    fn add(self, o: Mat2) -> Mat2 {
+        Mat2::new(self.xx + o.xx, self.xy + o.xy, self.yx + o.yx, self.yy + o.yy)
+    }
+}
+
+impl Sub for Mat2 {
+    type Output = Mat2;
+
+    fn sub(self, o: Mat2) -> Mat2 {
+        Mat2::new(self.xx - o.xx, self.xy - o.xy, self.yx - o.yx, self.yy - o.yy)
+    }
+}
+
+impl Mul for Mat2 {
+    type Output = Mat2;
+
+    fn mul(self, o: Mat2) -> Mat2 {
+        Mat2::new(
+            self.xx * o.xx + self.xy * o.yx,
+            self.xx * o.xy + self.xy * o.yy,
+            self.yx * o.xx + self.yy * o.yx,
+            self.yx * o.xy + self.yy * o.yy
+        )
+    }
+}
\ No newline at end of file
@@ -0,0 +1,278 @@
+use core_simd::*;
+use crate::particle::Particle;
+use crate::material::{Material, Plasticity};
+use crate::matrix_2::Mat2;
+use crate::rect::Rect;
+use crate::shape::Shape;
+use crate::spatial_hash::SpatialHash;
+use crate::fluid_sim::Properties;
+use crate::vector_2::*;
+
+// MLS-MPM (Hu et al. 2018) for elastoplastic materials like sand and snow
+// https://yuanming.taichi.graphics/publication/2018-mlsmpm/
+// https://github.com/taichi-dev/taichi/blob/master/python/taichi/examples/simulation/mpm88.py
+//
+// particles with a plastic material are advanced here instead of by the particle contact pass,
+// but still take part in the contacts so water and sand push each other around
+
+#[derive(Clone, Copy)]
+pub struct MpmNode {
+    pub vel: f32x2, // momentum during particle to grid, then velocity
+    pub mass: f32
+}
+
+pub struct MpmSolver {
+    pub cell_size: f32,
+    pub x_nodes: usize,
+    pub y_nodes: usize,
+    pub nodes: Vec<MpmNode>,
+    pub max_substep: f32, // explicit integration is only stable for small time steps
+    pub boundary_friction: f32, // coulomb friction against rects
+}
+
+impl MpmSolver {
+    // the grid covers the same world as the spatial hash
+    pub fn new(spatial_hash: &SpatialHash, cell_size: f32) -> MpmSolver {
+        let x_nodes = (spatial_hash.x_size as f32 / cell_size).ceil() as usize + 1;
+        let y_nodes = (spatial_hash.y_size as f32 / cell_size).ceil() as usize + 1;
+        MpmSolver {
+            cell_size,
+            x_nodes,
+            y_nodes,
+            nodes: vec![MpmNode { vel: vec2_from_single(0.0), mass: 0.0 }; x_nodes * y_nodes],
+            max_substep: 0.0005,
+            boundary_friction: 0.5
+        }
+    }
+
+    pub fn update(&mut self, particles: &mut Vec<Particle>, materials: &Vec<Material>, rects: &Vec<Rect>, properties: &Properties, dt: f32) {
+        if !particles.iter().any(|particle| materials[particle.material].uses_mpm()) {
+            return;
+        }
+
+        let substeps = (dt / self.max_substep).ceil().max(1.0) as usize;
+        let sub_dt = dt / substeps as f32;
+        for _i in 0..substeps {
+            self.particles_to_grid(particles, materials, properties, sub_dt);
+            self.update_grid(rects, properties, sub_dt);
+            self.grid_to_particles(particles, materials, sub_dt);
+        }
+    }
+
+    // quadratic b-spline weights for the 3x3 nodes around a particle
+    fn weights(&self, pos: f32x2) -> (isize, isize, f32x2, [f32x2; 3]) {
+        let grid_pos = pos / vec2_from_single(self.cell_size);
+        let base_x = (grid_pos[0] - 0.5).floor();
+        let base_y = (grid_pos[1] - 0.5).floor();
+        let fx = grid_pos - vec2(base_x, base_y);
+
+        let one_half = vec2_from_single(1.5) - fx;
+        let one = fx - vec2_from_single(1.0);
+        let half = fx - vec2_from_single(0.5);
+        let w = [
+            vec2_from_single(0.5) * one_half * one_half,
+            vec2_from_single(0.75) - one * one,
+            vec2_from_single(0.5) * half * half
+        ];
+        return (base_x as isize, base_y as isize, fx, w);
+    }
+
+    fn node_index(&self, x: isize, y: isize) -> Option<usize> {
+        if x < 0 || y < 0 || x as usize >= self.x_nodes || y as usize >= self.y_nodes {
+            return None;
+        }
+        return Some(x as usize + y as usize * self.x_nodes);
+    }
+
+    // kirchhoff stress for the particles deformation
+    fn stress(particle: &Particle, material: &Material) -> Mat2 {
+        let (mu, lambda) = material.lame_parameters();
+        let f = particle.deformation;
+
+        match material.plasticity {
+            Some(Plasticity::Snow { hardening, .. }) => {
+                // fixed corotated, stiffer when compacted
+                let h = (hardening * (1.0 - particle.plastic_j)).exp();
+                let (mu, lambda) = (mu * h, lambda * h);
+                let (u, sigma, v) = f.svd();
+                let r = u * v.transpose();
+                let j = sigma[0] * sigma[1];
+                return ((f - r) * f.transpose()).scale(2.0 * mu) + Mat2::identity().scale(lambda * j * (j - 1.0));
+            },
+            _ => {
+                // st venant kirchhoff with hencky strain
+                let (u, sigma, _v) = f.svd();
+                let epsilon = vec2(sigma[0].abs().max(1e-4).ln(), sigma[1].abs().max(1e-4).ln());
+                let trace = epsilon[0] + epsilon[1];
+                let tau = epsilon * vec2_from_single(2.0 * mu) + vec2_from_single(lambda * trace);
+                return u * Mat2::diagonal(tau) * u.transpose();
+            }
+        }
+    }
+
+    // project the deformation back onto the yield surface
+    fn apply_plasticity(particle: &mut Particle, material: &Material) {
+        let (u, sigma, v) = particle.deformation.svd();
+
+        match material.plasticity {
+            Some(Plasticity::Snow { critical_compression, critical_stretch, .. }) => {
+                let clamped = vec2(
+                    sigma[0].max(1.0 - critical_compression).min(1.0 + critical_stretch),
+                    sigma[1].max(1.0 - critical_compression).min(1.0 + critical_stretch)
+                );
+                particle.plastic_j *= (sigma[0] * sigma[1]) / (clamped[0] * clamped[1]);
+                particle.deformation = u * Mat2::diagonal(clamped) * v.transpose();
+            },
+            Some(Plasticity::DruckerPrager { friction_angle }) => {
+                let (mu, lambda) = material.lame_parameters();
+                let sin_phi = friction_angle.to_radians().sin();
+                let alpha = (2.0 as f32 / 3.0).sqrt() * 2.0 * sin_phi / (3.0 - sin_phi);
+
+                let epsilon = vec2(sigma[0].abs().max(1e-4).ln(), sigma[1].abs().max(1e-4).ln());
+                let trace = epsilon[0] + epsilon[1];
+                if trace >= 0.0 {
+                    // pulled apart, sand has no cohesion so project to the tip of the cone
+                    particle.deformation = u * v.transpose();
+                    return;
+                }
+
+                let deviatoric = epsilon - vec2_from_single(trace * 0.5);
+                let deviatoric_len = length_squared(deviatoric).sqrt();
+                let delta_gamma = deviatoric_len + (2.0 * lambda + 2.0 * mu) / (2.0 * mu) * trace * alpha;
+                if deviatoric_len <= 0.0 || delta_gamma <= 0.0 {
+                    // inside the yield surface
+                    return;
+                }
+
+                let projected = epsilon - deviatoric * vec2_from_single(delta_gamma / deviatoric_len);
+                let new_sigma = vec2(projected[0].exp(), projected[1].exp());
+                particle.deformation = u * Mat2::diagonal(new_sigma) * v.transpose();
+            },
+            None => {}
+        }
+    }
+
+    fn particles_to_grid(&mut self, particles: &Vec<Particle>, materials: &Vec<Material>, properties: &Properties, dt: f32) {
+        for node in self.nodes.iter_mut() {
+            node.vel = vec2_from_single(0.0);
+            node.mass = 0.0;
+        }
+
+        // each particle represents the area between it and its neighbours at rest spacing, with a density of 1
+        let spacing = properties.radius * 2.0;
+        let volume = spacing * spacing;
+        let mass = volume;
+        let inv_dx = 1.0 / self.cell_size;
+
+        for particle in particles.iter() {
+            let material = &materials[particle.material];
+            if !material.uses_mpm() {
+                continue;
+            }
+
+            let stress = MpmSolver::stress(particle, material);
+            let affine = stress.scale(-dt * volume * 4.0 * inv_dx * inv_dx) + particle.affine.scale(mass);
+
+            let (base_x, base_y, fx, w) = self.weights(particle.pos);
+            for i in 0..3 {
+                for j in 0..3 {
+                    let index = match self.node_index(base_x + i as isize, base_y + j as isize) {
+                        Some(index) => index,
+                        None => continue
+                    };
+
+                    let dpos = (vec2(i as f32, j as f32) - fx) * vec2_from_single(self.cell_size);
+                    let weight = w[i][0] * w[j][1];
+                    let node = &mut self.nodes[index];
+                    node.vel += (particle.vel * vec2_from_single(mass) + affine.mul_vec(dpos)) * vec2_from_single(weight);
+                    node.mass += weight * mass;
+                }
+            }
+        }
+    }
+
+    fn update_grid(&mut self, rects: &Vec<Rect>, properties: &Properties, dt: f32) {
+        const BOUNDARY: usize = 2;
+
+        for y in 0..self.y_nodes {
+            for x in 0..self.x_nodes {
+                let node_pos = vec2(x as f32, y as f32) * vec2_from_single(self.cell_size);
+                let node = &mut self.nodes[x + y * self.x_nodes];
+                if node.mass <= 0.0 {
+                    continue;
+                }
+
+                node.vel = node.vel / vec2_from_single(node.mass);
+                node.vel += properties.gravity * vec2_from_single(dt);
+
+                // sticky on the way in, free to leave
+                if (x < BOUNDARY && node.vel[0] < 0.0) || (x + BOUNDARY >= self.x_nodes && node.vel[0] > 0.0) {
+                    node.vel[0] = 0.0;
+                }
+                if (y < BOUNDARY && node.vel[1] < 0.0) || (y + BOUNDARY >= self.y_nodes && node.vel[1] > 0.0) {
+                    node.vel[1] = 0.0;
+                }
+
+                // TODO: spatial hashing
+                for rect in rects.iter() {
+                    let (dist, normal) = rect.signed_distance(node_pos);
+                    if dist >= 0.0 {
+                        continue;
+                    }
+
+                    let normal_vel = dot(node.vel, normal);
+                    if normal_vel >= 0.0 {
+                        continue;
+                    }
+
+                    // remove the velocity into the rect and apply friction to what is left
+                    let tangent_vel = node.vel - normal * vec2_from_single(normal_vel);
+                    let tangent_speed = length_squared(tangent_vel).sqrt();
+                    let friction = -self.boundary_friction * normal_vel;
+                    node.vel = if tangent_speed <= friction {
+                        vec2_from_single(0.0)
+                    } else {
+                        tangent_vel * vec2_from_single(1.0 - friction / tangent_speed)
+                    };
+                }
+            }
+        }
+    }
+
+    fn grid_to_particles(&mut self, particles: &mut Vec<Particle>, materials: &Vec<Material>, dt: f32) {
+        let inv_dx = 1.0 / self.cell_size;
+        let min_pos = vec2_from_single(self.cell_size);
+        let max_pos = vec2((self.x_nodes - 2) as f32, (self.y_nodes - 2) as f32) * vec2_from_single(self.cell_size);
+
+        for particle in particles.iter_mut() {
+            let material = &materials[particle.material];
+            if !material.uses_mpm() {
+                continue;
+            }
+
+            let (base_x, base_y, fx, w) = self.weights(particle.pos);
+            let mut vel = vec2_from_single(0.0);
+            let mut affine = Mat2::zero();
+            for i in 0..3 {
+                for j in 0..3 {
+                    let index = match self.node_index(base_x + i as isize, base_y + j as isize) {
+                        Some(index) => index,
+                        None => continue
+                    };
+
+                    let dpos = (vec2(i as f32, j as f32) - fx) * vec2_from_single(self.cell_size);
+                    let weight = w[i][0] * w[j][1];
+                    let node_vel = self.nodes[index].vel;
+                    vel += node_vel * vec2_from_single(weight);
+                    affine = affine + Mat2::outer(node_vel, dpos).scale(4.0 * weight * inv_dx * inv_dx);
+                }
+            }
+
+            particle.vel = vel;
+            particle.affine = affine;
+            particle.pos = (particle.pos + vel * vec2_from_single(dt)).clamp(min_pos, max_pos);
+            particle.deformation = (Mat2::identity() + affine.scale(dt)) * particle.deformation;
+            MpmSolver::apply_plasticity(particle, material);
+        }
+    }
+}
\ No newline at end of file
@@ -2,8 +2,15 @@ use core_simd::*;
 use crate::spatial_hash::SpatialHash;
 use crate::spatial_hash_iter::SpatialHashIter;
 use crate::fluid_sim::Properties;
+use crate::material::{Material, MaterialId, ViscosityModel};
+use crate::force_field::ForceField;
+use crate::kernel;
+use crate::matrix_2::Mat2;
+use crate::cluster::ClusterId;
 use crate::vector_2::*;
 
+pub type ParticleId = usize;
+
 #[derive(Clone)]
 pub struct Contact {
     //pub pos: f32x2,
@@ -14,24 +21,73 @@ pub struct Contact {
 
 #[derive(Clone)]
 pub struct Particle {
+    pub id: ParticleId, // assigned by the FluidSim when the particle is added
     pub pos: f32x2,
     pub vel: f32x2,
+    pub material: MaterialId,
+    pub density: f32, // kernel weighted sum of the neighbouring contacts
+    pub normal: f32x2, // surface normal, zero for particles inside the fluid
+    pub deformation: Mat2, // elastic deformation gradient, used by the MpmSolver
+    pub affine: Mat2, // APIC affine velocity field, used by the MpmSolver
+    pub plastic_j: f32, // volume change from plastic deformation, used to harden snow
+    pub temperature: f32, // NaN until set, then the FluidSim gives it the ambient temperature when it is added
+    pub cluster: Option<ClusterId>,
+    pub age: f32, // seconds since the particle was added, used to fade out materials with a lifetime
     pub contacts: Vec<Contact>,
 }
 
 impl Particle {
     pub fn new(pos: f32x2) -> Particle {
         Particle{
+            id: 0,
             pos,
             vel: Simd::from_array([0.0, 0.0]),
+            material: 0,
+            density: 0.0,
+            normal: vec2_from_single(0.0),
+            deformation: Mat2::identity(),
+            affine: Mat2::zero(),
+            plastic_j: 1.0,
+            temperature: f32::NAN,
+            cluster: None,
+            age: 0.0,
             contacts: Vec::new()
         }
     }
 
     pub fn with_vel(pos: f32x2, vel: f32x2) -> Particle {
         Particle{
+            id: 0,
             pos,
             vel,
+            material: 0,
+            density: 0.0,
+            normal: vec2_from_single(0.0),
+            deformation: Mat2::identity(),
+            affine: Mat2::zero(),
+            plastic_j: 1.0,
+            temperature: f32::NAN,
+            cluster: None,
+            age: 0.0,
+            contacts: Vec::new()
+        }
+    }
+
+    // contacts hold pointers into the particle list they came from, so copies made to keep around should leave them out
+    pub fn without_contacts(&self) -> Particle {
+        Particle{
+            id: self.id,
+            pos: self.pos,
+            vel: self.vel,
+            material: self.material,
+            density: self.density,
+            normal: self.normal,
+            deformation: self.deformation,
+            affine: self.affine,
+            plastic_j: self.plastic_j,
+            temperature: self.temperature,
+            cluster: self.cluster,
+            age: self.age,
             contacts: Vec::new()
         }
     }
@@ -41,7 +97,10 @@ impl Particle {
     // https://www.gamedeveloper.com/disciplines/simple-intersection-tests-for-games
     //
     #[inline(always)]
-    pub fn check_particle_collisions(&mut self, cell_it: &SpatialHashIter, properties: &Properties, _dt: f32x2) {
+    pub fn check_particle_collisions(&mut self, cell_it: &SpatialHashIter, properties: &Properties, materials: &Vec<Material>, _dt: f32x2) {
+        let is_mpm = materials[self.material].uses_mpm();
+        let repulsion = materials[self.material].repulsion;
+
         // clear last frame contacts
         self.contacts.clear();
 
@@ -76,6 +135,11 @@ impl Particle {
                         particle: col_particle
                     });
 
+                    // the mpm grid handles contact between plastic particles
+                    if is_mpm && materials[(*col_particle).material].uses_mpm() {
+                        continue;
+                    }
+
                     // TODO: should this conntriute towards an 'instantanous push' amount
                     // which is different to velocity?
                     let dist_to_move = dist * 0.5;
@@ -88,18 +152,177 @@ impl Particle {
                     let vel_m: f32x2 = Simd::from_array([vel_mag, vel_mag]);
 
                     // lose or gain energy in the outgoing velocity
-                    let vel = (pos_delta * vel_m) * vec2_from_single(properties.elasticity);
+                    let vel = (pos_delta * vel_m) * vec2_from_single(properties.elasticity * repulsion);
 
                     self.vel -= vel;
                 }
             }
         }
+
+        // density from this particle and its contacts
+        let h = properties.radius * 2.0;
+        self.density = kernel::poly6(0.0, h);
+        for contact in &self.contacts {
+            self.density += kernel::poly6(contact.depth, h);
+        }
+    }
+
+    // needs the density of all neighbours, so can only be done once all contacts are computed
+    // n_i = h * sum(1 / rho_j * grad W)
+    #[inline(always)]
+    pub fn compute_normal(&mut self, properties: &Properties) {
+        let h = properties.radius * 2.0;
+        let mut normal = vec2_from_single(0.0);
+        for contact in &self.contacts {
+            unsafe {
+                let density = (*contact.particle).density;
+                if density <= 0.0 {
+                    continue;
+                }
+
+                // grad W = W'(r) * (x_i - x_j) / r, and the contact normal is (x_j - x_i) / r
+                normal -= contact.normal * vec2_from_single(kernel::spiky_gradient(contact.depth, h) / density);
+            }
+        }
+        self.normal = normal * vec2_from_single(h);
+    }
+
+    // Akinci cohesion + curvature, pulls small volumes into droplets
+    #[inline(always)]
+    pub fn apply_surface_tension(&mut self, properties: &Properties, dt: f32x2) {
+        if properties.surface_tension <= 0.0 {
+            return;
+        }
+
+        let h = properties.radius * 2.0;
+        let mut force = vec2_from_single(0.0);
+        for contact in &self.contacts {
+            unsafe {
+                let cohesion = contact.normal * vec2_from_single(kernel::cohesion(contact.depth, h));
+                let curvature = -(self.normal - (*contact.particle).normal);
+                force += (cohesion + curvature) * vec2_from_single(properties.surface_tension);
+            }
+        }
+        self.vel += force * dt;
+    }
+
+    // heat flowing in from the neighbouring particles
+    // computed for every particle before any temperatures are changed
+    #[inline(always)]
+    pub fn heat_flow(&self, properties: &Properties, materials: &Vec<Material>, dt: f32) -> f32 {
+        let conductivity = materials[self.material].conductivity;
+        if conductivity <= 0.0 {
+            return 0.0;
+        }
+
+        let h = properties.radius * 2.0;
+        let mut flow = 0.0;
+        for contact in &self.contacts {
+            unsafe {
+                let other = &*contact.particle;
+                let other_conductivity = materials[other.material].conductivity;
+                if other_conductivity <= 0.0 || other.density <= 0.0 {
+                    continue;
+                }
+
+                // harmonic mean so an insulator next to a conductor lets little heat through
+                let k = 2.0 * conductivity * other_conductivity / (conductivity + other_conductivity);
+                let blend = (k * dt * kernel::viscosity_laplacian(contact.depth, h) / other.density).min(0.25);
+                flow += blend * (other.temperature - self.temperature);
+            }
+        }
+        return flow;
+    }
+
+    // hot particles are lighter than their surroundings so are pushed against gravity
+    #[inline(always)]
+    pub fn buoyancy(&self, properties: &Properties, material: &Material) -> f32x2 {
+        return -properties.gravity * vec2_from_single(material.expansion * (self.temperature - properties.ambient_temperature));
     }
 
+    // laplacian viscosity using the material's viscosity model, followed by XSPH velocity smoothing
     #[inline(always)]
-    pub fn update_velocity(&mut self, _spatial_hash: &SpatialHash, properties: &Properties, dt: f32x2) {
+    pub fn apply_viscosity(&mut self, properties: &Properties, materials: &Vec<Material>, dt: f32x2) {
+        let material = &materials[self.material];
+        if self.contacts.is_empty() || (material.viscosity_model == ViscosityModel::Newtonian && material.viscosity <= 0.0 && material.xsph <= 0.0) {
+            return;
+        }
+
+        // estimate the shear rate from how fast the neighbours are moving relative to us
+        let mut shear_rate = 0.0;
+        for contact in &self.contacts {
+            unsafe {
+                let rel_vel = (*contact.particle).vel - self.vel;
+                shear_rate += length_squared(rel_vel).sqrt() / contact.depth.max(0.0001);
+            }
+        }
+        shear_rate /= self.contacts.len() as f32;
+
+        let viscosity = material.viscosity_model.effective_viscosity(material.viscosity, shear_rate);
+
+        let h = properties.radius * 2.0;
+        let mut visc_delta = vec2_from_single(0.0);
+        let mut xsph_delta = vec2_from_single(0.0);
+        for contact in &self.contacts {
+            unsafe {
+                let other = &*contact.particle;
+                if other.density <= 0.0 {
+                    continue;
+                }
+
+                let rel_vel = other.vel - self.vel;
+
+                // clamped so a very viscous material can't overshoot its neighbours velocity
+                let visc_blend = (viscosity * dt[0] * kernel::viscosity_laplacian(contact.depth, h) / other.density).min(0.5);
+                visc_delta += rel_vel * vec2_from_single(visc_blend);
+
+                xsph_delta += rel_vel * vec2_from_single(kernel::poly6(contact.depth, h) / other.density);
+            }
+        }
+
+        self.vel += visc_delta + xsph_delta * vec2_from_single(material.xsph);
+    }
+
+    // 1 when new, fading to 0 at the end of the materials lifetime
+    pub fn alpha(&self, material: &Material) -> f32 {
+        return match material.lifetime {
+            Some(lifetime) if lifetime > 0.0 => (1.0 - self.age / lifetime).max(0.0).min(1.0),
+            _ => 1.0
+        };
+    }
+
+    #[inline(always)]
+    pub fn update_velocity(&mut self, _spatial_hash: &SpatialHash, properties: &Properties, materials: &Vec<Material>, force_fields: &Vec<ForceField>, dt: f32x2) {
+        // global damping, the fraction of velocity kept each second
+        if properties.damping < 1.0 {
+            self.vel *= vec2_from_single(properties.damping.powf(dt[0]));
+        }
+
+        let material = &materials[self.material];
+
         // add gravity
-        self.vel += properties.gravity * dt;
+        self.vel += properties.gravity * vec2_from_single(material.gravity_scale) * dt;
+
+        self.vel += self.buoyancy(properties, material) * dt;
+
+        // drift with the wind
+        if material.drag > 0.0 {
+            let blend = 1.0 - (-material.drag * dt[0]).exp();
+            self.vel += (properties.wind - self.vel) * vec2_from_single(blend);
+        }
+
+        // add any external forces
+        for force_field in force_fields.iter() {
+            self.vel += force_field.force(self) * dt;
+        }
+
+        self.apply_surface_tension(properties, dt);
+        self.apply_viscosity(properties, materials, dt);
+
+        // gases only get the weak push from check_particle_collisions
+        if material.is_gas() {
+            return;
+        }
 
         // iterate over contacts and modify velocity
         for contact in &self.contacts {
@@ -0,0 +1,614 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use core_simd::*;
+use crate::fluid_sim::FluidSim;
+use crate::particle::Particle;
+use crate::material::MaterialId;
+use crate::vector_2::*;
+
+// baked particle data for offline playback
+//
+// file layout, all little endian:
+//   header: magic, version, world size, material count, attribute flags, quantise flag, max speed, frames per chunk
+//   chunks: each is a run of frames, every frame starts with its byte length so frames can be skipped without decoding
+//   index: the file offset of each chunk, followed by the frame count, the offset of the index and the magic again
+//
+// the index is written last so a reader can seek straight to the chunk holding any frame
+
+const MAGIC: &[u8; 4] = b"FSPC";
+const VERSION: u32 = 2;
+
+const ATTRIBUTE_VELOCITY: u32 = 1;
+const ATTRIBUTE_MATERIAL: u32 = 2;
+const ATTRIBUTE_TEMPERATURE: u32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CacheOptions {
+    pub velocity: bool,
+    pub material: bool,
+    pub temperature: bool,
+    pub quantise: bool, // store positions and velocities as 16 bits each instead of 32
+    pub max_speed: f32, // velocities are clamped to this when quantised
+    pub frames_per_chunk: usize,
+}
+
+impl CacheOptions {
+    pub fn new() -> CacheOptions {
+        CacheOptions {
+            velocity: true,
+            material: true,
+            temperature: false,
+            quantise: false,
+            max_speed: 100.0,
+            frames_per_chunk: 32
+        }
+    }
+
+    fn attributes(&self) -> u32 {
+        let mut attributes = 0;
+        if self.velocity {
+            attributes |= ATTRIBUTE_VELOCITY;
+        }
+        if self.material {
+            attributes |= ATTRIBUTE_MATERIAL;
+        }
+        if self.temperature {
+            attributes |= ATTRIBUTE_TEMPERATURE;
+        }
+        return attributes;
+    }
+
+    fn from_attributes(attributes: u32, quantise: bool, max_speed: f32, frames_per_chunk: usize) -> CacheOptions {
+        CacheOptions {
+            velocity: attributes & ATTRIBUTE_VELOCITY != 0,
+            material: attributes & ATTRIBUTE_MATERIAL != 0,
+            temperature: attributes & ATTRIBUTE_TEMPERATURE != 0,
+            quantise,
+            max_speed,
+            frames_per_chunk
+        }
+    }
+}
+
+// one frame of particles, any attribute that was not stored is left empty
+#[derive(Clone, Debug)]
+pub struct CacheFrame {
+    pub time: f32,
+    pub positions: Vec<f32x2>,
+    pub velocities: Vec<f32x2>,
+    pub materials: Vec<MaterialId>,
+    pub temperatures: Vec<f32>,
+}
+
+impl CacheFrame {
+    pub fn from_particles(particles: &Vec<Particle>, time: f32) -> CacheFrame {
+        CacheFrame {
+            time,
+            positions: particles.iter().map(|particle| particle.pos).collect(),
+            velocities: particles.iter().map(|particle| particle.vel).collect(),
+            materials: particles.iter().map(|particle| particle.material).collect(),
+            temperatures: particles.iter().map(|particle| particle.temperature).collect()
+        }
+    }
+
+    pub fn to_particles(&self) -> Vec<Particle> {
+        let mut particles: Vec<Particle> = Vec::with_capacity(self.positions.len());
+        for (i, pos) in self.positions.iter().enumerate() {
+            let mut particle = Particle::new(*pos);
+            particle.id = i;
+            if let Some(vel) = self.velocities.get(i) {
+                particle.vel = *vel;
+            }
+            if let Some(material) = self.materials.get(i) {
+                particle.material = *material;
+            }
+            if let Some(temperature) = self.temperatures.get(i) {
+                particle.temperature = *temperature;
+            }
+            particles.push(particle);
+        }
+        return particles;
+    }
+}
+
+// maps a value in the range min..max to 0..65535
+fn quantise(v: f32, min: f32, max: f32) -> u16 {
+    let t = ((v - min) / (max - min)).max(0.0).min(1.0);
+    return (t * u16::MAX as f32).round() as u16;
+}
+
+fn dequantise(q: u16, min: f32, max: f32) -> f32 {
+    return min + (q as f32 / u16::MAX as f32) * (max - min);
+}
+
+// the sim keeps particles just inside the world, see FluidSim::move_particles
+// quantised positions use this as their range so a particle on the far edge can't decode to outside the spatial hash
+fn max_position(world_size: f32x2) -> f32x2 {
+    return world_size - vec2_from_single(0.001);
+}
+
+fn io_error(e: std::io::Error) -> String {
+    return format!("particle cache io error: {}", e);
+}
+
+pub struct ParticleCacheWriter {
+    file: BufWriter<File>,
+    options: CacheOptions,
+    world_size: f32x2,
+    material_count: usize,
+    chunk_offsets: Vec<u64>,
+    frame_count: usize,
+}
+
+impl ParticleCacheWriter {
+    pub fn create(path: &Path, fluid_sim: &FluidSim, options: CacheOptions) -> Result<ParticleCacheWriter, String> {
+        if options.frames_per_chunk == 0 {
+            return Err("frames_per_chunk must be at least 1".to_string());
+        }
+
+        let file = File::create(path).map_err(|e| format!("failed to create {}: {}", path.display(), e))?;
+        let mut writer = ParticleCacheWriter {
+            file: BufWriter::new(file),
+            options,
+            world_size: vec2(fluid_sim.spatial_hash.x_size as f32, fluid_sim.spatial_hash.y_size as f32),
+            material_count: fluid_sim.materials.len(),
+            chunk_offsets: vec![],
+            frame_count: 0
+        };
+
+        let mut header: Vec<u8> = Vec::new();
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&(fluid_sim.spatial_hash.x_size as u32).to_le_bytes());
+        header.extend_from_slice(&(fluid_sim.spatial_hash.y_size as u32).to_le_bytes());
+        header.extend_from_slice(&(writer.material_count as u32).to_le_bytes());
+        header.extend_from_slice(&options.attributes().to_le_bytes());
+        header.push(options.quantise as u8);
+        header.extend_from_slice(&options.max_speed.to_le_bytes());
+        header.extend_from_slice(&(options.frames_per_chunk as u32).to_le_bytes());
+        writer.file.write_all(&header).map_err(io_error)?;
+        return Ok(writer);
+    }
+
+    pub fn frame_count(&self) -> usize {
+        return self.frame_count;
+    }
+
+    pub fn write_frame(&mut self, fluid_sim: &FluidSim, time: f32) -> Result<(), String> {
+        return self.write_cache_frame(&CacheFrame::from_particles(&fluid_sim.particles, time));
+    }
+
+    pub fn write_cache_frame(&mut self, frame: &CacheFrame) -> Result<(), String> {
+        if let Some(material) = frame.materials.iter().find(|material| **material >= self.material_count) {
+            return Err(format!("frame uses material {} but the cache was created with {} materials", material, self.material_count));
+        }
+        if self.frame_count % self.options.frames_per_chunk == 0 {
+            let offset = self.file.stream_position().map_err(io_error)?;
+            self.chunk_offsets.push(offset);
+        }
+
+        let data = self.encode(frame);
+        self.file.write_all(&(data.len() as u32).to_le_bytes()).map_err(io_error)?;
+        self.file.write_all(&data).map_err(io_error)?;
+        self.frame_count += 1;
+        return Ok(());
+    }
+
+    // must be called once all frames are written, without the index the cache can't be read
+    pub fn finish(mut self) -> Result<(), String> {
+        let index_offset = self.file.stream_position().map_err(io_error)?;
+        let mut index: Vec<u8> = Vec::new();
+        for offset in self.chunk_offsets.iter() {
+            index.extend_from_slice(&offset.to_le_bytes());
+        }
+        index.extend_from_slice(&(self.frame_count as u32).to_le_bytes());
+        index.extend_from_slice(&index_offset.to_le_bytes());
+        index.extend_from_slice(MAGIC);
+        self.file.write_all(&index).map_err(io_error)?;
+        return self.file.flush().map_err(io_error);
+    }
+
+    fn encode(&self, frame: &CacheFrame) -> Vec<u8> {
+        let count = frame.positions.len();
+        let mut data: Vec<u8> = Vec::new();
+        data.extend_from_slice(&frame.time.to_le_bytes());
+        data.extend_from_slice(&(count as u32).to_le_bytes());
+
+        let max_pos = max_position(self.world_size);
+        for pos in frame.positions.iter() {
+            if self.options.quantise {
+                data.extend_from_slice(&quantise(pos[0], 0.0, max_pos[0]).to_le_bytes());
+                data.extend_from_slice(&quantise(pos[1], 0.0, max_pos[1]).to_le_bytes());
+            }
+            else {
+                data.extend_from_slice(&pos[0].to_le_bytes());
+                data.extend_from_slice(&pos[1].to_le_bytes());
+            }
+        }
+
+        if self.options.velocity {
+            let max_speed = self.options.max_speed;
+            for i in 0..count {
+                let vel = frame.velocities.get(i).copied().unwrap_or(vec2_from_single(0.0));
+                if self.options.quantise {
+                    data.extend_from_slice(&quantise(vel[0], -max_speed, max_speed).to_le_bytes());
+                    data.extend_from_slice(&quantise(vel[1], -max_speed, max_speed).to_le_bytes());
+                }
+                else {
+                    data.extend_from_slice(&vel[0].to_le_bytes());
+                    data.extend_from_slice(&vel[1].to_le_bytes());
+                }
+            }
+        }
+
+        if self.options.material {
+            for i in 0..count {
+                let material = frame.materials.get(i).copied().unwrap_or(0);
+                data.extend_from_slice(&(material as u16).to_le_bytes());
+            }
+        }
+
+        if self.options.temperature {
+            for i in 0..count {
+                let temperature = frame.temperatures.get(i).copied().unwrap_or(0.0);
+                data.extend_from_slice(&temperature.to_le_bytes());
+            }
+        }
+
+        return data;
+    }
+}
+
+pub struct ParticleCacheReader {
+    file: BufReader<File>,
+    pub options: CacheOptions,
+    pub world_size: (usize, usize),
+    pub material_count: usize, // the scene played back with must have at least this many materials
+    chunk_offsets: Vec<u64>,
+    index_offset: u64, // frames all lie between the header and the index
+    frame_count: usize,
+}
+
+// reads little endian values from a byte slice, advancing through it
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.pos + len > self.data.len() {
+            return Err("particle cache frame is truncated".to_string());
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        return Ok(bytes);
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        return Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()));
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        return Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()));
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        return Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()));
+    }
+
+    fn f32(&mut self) -> Result<f32, String> {
+        return Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()));
+    }
+}
+
+impl ParticleCacheReader {
+    pub fn open(path: &Path) -> Result<ParticleCacheReader, String> {
+        let file = File::open(path).map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+        let mut file = BufReader::new(file);
+
+        const HEADER_SIZE: usize = 4 + 4 + 4 + 4 + 4 + 4 + 1 + 4 + 4;
+        let mut header = [0u8; HEADER_SIZE];
+        file.read_exact(&mut header).map_err(|_| format!("{} is not a particle cache", path.display()))?;
+        let mut cursor = Cursor { data: &header, pos: 0 };
+        if cursor.take(4)? != MAGIC {
+            return Err(format!("{} is not a particle cache", path.display()));
+        }
+        let version = cursor.u32()?;
+        if version != VERSION {
+            return Err(format!("{} is particle cache version {}, expected {}", path.display(), version, VERSION));
+        }
+        let x_size = cursor.u32()? as usize;
+        let y_size = cursor.u32()? as usize;
+        let material_count = cursor.u32()? as usize;
+        let attributes = cursor.u32()?;
+        let quantise = cursor.take(1)?[0] != 0;
+        let max_speed = cursor.f32()?;
+        let frames_per_chunk = cursor.u32()? as usize;
+        if frames_per_chunk == 0 {
+            return Err(format!("{} has frames_per_chunk of 0", path.display()));
+        }
+
+        // the footer tells us where the index is
+        const FOOTER_SIZE: usize = 4 + 8 + 4;
+        let mut footer = [0u8; FOOTER_SIZE];
+        let file_len = file.seek(SeekFrom::End(0)).map_err(io_error)?;
+        if file_len < (HEADER_SIZE + FOOTER_SIZE) as u64 {
+            return Err(format!("{} has no index, was the writer finished?", path.display()));
+        }
+        file.seek(SeekFrom::End(-(FOOTER_SIZE as i64))).map_err(io_error)?;
+        file.read_exact(&mut footer).map_err(io_error)?;
+        let mut cursor = Cursor { data: &footer, pos: 0 };
+        let frame_count = cursor.u32()? as usize;
+        let index_offset = cursor.u64()?;
+        if cursor.take(4)? != MAGIC {
+            return Err(format!("{} has no index, was the writer finished?", path.display()));
+        }
+
+        // sizes come from the file, so check them against its length before allocating anything
+        let chunk_count = (frame_count + frames_per_chunk - 1) / frames_per_chunk;
+        let index_end = index_offset.checked_add(chunk_count as u64 * 8);
+        if index_offset < HEADER_SIZE as u64 || index_end != Some(file_len - FOOTER_SIZE as u64) {
+            return Err(format!("{} has a corrupt index", path.display()));
+        }
+        let mut index = vec![0u8; chunk_count * 8];
+        file.seek(SeekFrom::Start(index_offset)).map_err(io_error)?;
+        file.read_exact(&mut index).map_err(io_error)?;
+        let mut cursor = Cursor { data: &index, pos: 0 };
+        let mut chunk_offsets: Vec<u64> = Vec::with_capacity(chunk_count);
+        for _i in 0..chunk_count {
+            let offset = cursor.u64()?;
+            if offset < HEADER_SIZE as u64 || offset > index_offset {
+                return Err(format!("{} has a corrupt index", path.display()));
+            }
+            chunk_offsets.push(offset);
+        }
+
+        Ok(ParticleCacheReader {
+            file,
+            options: CacheOptions::from_attributes(attributes, quantise, max_speed, frames_per_chunk),
+            world_size: (x_size, y_size),
+            material_count,
+            chunk_offsets,
+            index_offset,
+            frame_count
+        })
+    }
+
+    pub fn frame_count(&self) -> usize {
+        return self.frame_count;
+    }
+
+    // seeks to the chunk holding the frame then skips over the frames before it
+    pub fn read_frame(&mut self, index: usize) -> Result<CacheFrame, String> {
+        if index >= self.frame_count {
+            return Err(format!("frame {} is out of range, the cache has {} frames", index, self.frame_count));
+        }
+
+        let chunk = index / self.options.frames_per_chunk;
+        self.file.seek(SeekFrom::Start(self.chunk_offsets[chunk])).map_err(io_error)?;
+        for _i in 0..(index % self.options.frames_per_chunk) {
+            let len = self.read_frame_len()?;
+            self.file.seek_relative(len as i64).map_err(io_error)?;
+        }
+
+        let len = self.read_frame_len()?;
+        let mut data = vec![0u8; len];
+        self.file.read_exact(&mut data).map_err(io_error)?;
+        return self.decode(&data);
+    }
+
+    // the length is checked against the index so a corrupt frame can't run past the frame data
+    fn read_frame_len(&mut self) -> Result<usize, String> {
+        let mut len = [0u8; 4];
+        self.file.read_exact(&mut len).map_err(io_error)?;
+        let len = u32::from_le_bytes(len) as usize;
+        let pos = self.file.stream_position().map_err(io_error)?;
+        if pos + len as u64 > self.index_offset {
+            return Err("particle cache frame is truncated".to_string());
+        }
+        return Ok(len);
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<CacheFrame, String> {
+        let mut cursor = Cursor { data, pos: 0 };
+        let time = cursor.f32()?;
+        let count = cursor.u32()? as usize;
+        // every particle takes at least 4 bytes, a larger count can't be right
+        if count > data.len() / 4 {
+            return Err("particle cache frame is truncated".to_string());
+        }
+        let world_size = vec2(self.world_size.0 as f32, self.world_size.1 as f32);
+        let max_pos = max_position(world_size);
+        let max_speed = self.options.max_speed;
+
+        let mut frame = CacheFrame {
+            time,
+            positions: Vec::with_capacity(count),
+            velocities: vec![],
+            materials: vec![],
+            temperatures: vec![]
+        };
+
+        for _i in 0..count {
+            let pos = if self.options.quantise {
+                vec2(dequantise(cursor.u16()?, 0.0, max_pos[0]), dequantise(cursor.u16()?, 0.0, max_pos[1]))
+            }
+            else {
+                vec2(cursor.f32()?, cursor.f32()?)
+            };
+            // the particles are spatial hashed on playback, so must be inside the world
+            if !(pos[0] >= 0.0 && pos[1] >= 0.0 && pos[0] < world_size[0] && pos[1] < world_size[1]) {
+                return Err(format!("particle cache frame has a particle at {:?} outside the {}x{} world", pos, self.world_size.0, self.world_size.1));
+            }
+            frame.positions.push(pos);
+        }
+
+        if self.options.velocity {
+            for _i in 0..count {
+                let vel = if self.options.quantise {
+                    vec2(dequantise(cursor.u16()?, -max_speed, max_speed), dequantise(cursor.u16()?, -max_speed, max_speed))
+                }
+                else {
+                    vec2(cursor.f32()?, cursor.f32()?)
+                };
+                frame.velocities.push(vel);
+            }
+        }
+
+        if self.options.material {
+            for _i in 0..count {
+                let material = cursor.u16()? as MaterialId;
+                if material >= self.material_count {
+                    return Err(format!("particle cache frame uses material {} but the cache has {} materials", material, self.material_count));
+                }
+                frame.materials.push(material);
+            }
+        }
+
+        if self.options.temperature {
+            for _i in 0..count {
+                frame.temperatures.push(cursor.f32()?);
+            }
+        }
+
+        return Ok(frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use crate::fluid_sim::FluidSim;
+    use crate::particle::Particle;
+    use crate::particle_cache::{CacheOptions, CacheFrame, ParticleCacheWriter, ParticleCacheReader};
+    use crate::vector_2::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        return std::env::temp_dir().join(format!("fluidic_space_{}_{}.fspc", name, std::process::id()));
+    }
+
+    fn write_cache(name: &str, frames: usize) -> PathBuf {
+        let path = temp_path(name);
+        let mut fluid_sim = FluidSim::with_seed(20, 10, 0);
+        fluid_sim.add_particles(&vec![Particle::new(vec2(5.0, 5.0)), Particle::new(vec2(6.0, 5.0))]);
+        let mut options = CacheOptions::new();
+        options.frames_per_chunk = 2;
+        let mut writer = ParticleCacheWriter::create(&path, &fluid_sim, options).unwrap();
+        for i in 0..frames {
+            writer.write_frame(&fluid_sim, i as f32).unwrap();
+        }
+        writer.finish().unwrap();
+        return path;
+    }
+
+    fn open_err(path: &Path) -> String {
+        return match ParticleCacheReader::open(path) {
+            Ok(_) => panic!("expected {} to be rejected", path.display()),
+            Err(e) => e
+        };
+    }
+
+    #[test]
+    fn round_trips_frames() {
+        let path = write_cache("round_trip", 5);
+        let mut reader = ParticleCacheReader::open(&path).unwrap();
+        assert_eq!(reader.frame_count(), 5);
+        assert_eq!(reader.material_count, 1);
+        let frame = reader.read_frame(3).unwrap();
+        assert_eq!(frame.time, 3.0);
+        assert_eq!(frame.positions, vec![vec2(5.0, 5.0), vec2(6.0, 5.0)]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn quantised_particles_on_the_far_edge_stay_inside_the_world() {
+        let path = temp_path("quantised_edge");
+        let mut fluid_sim = FluidSim::with_seed(100, 100, 0);
+        fluid_sim.add_particles(&vec![Particle::new(vec2(50.0, 99.9997)), Particle::new(vec2(99.9999, 0.0))]);
+        let mut options = CacheOptions::new();
+        options.quantise = true;
+        let mut writer = ParticleCacheWriter::create(&path, &fluid_sim, options).unwrap();
+        writer.write_frame(&fluid_sim, 0.0).unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = ParticleCacheReader::open(&path).unwrap();
+        let frame = reader.read_frame(0).unwrap();
+        for pos in frame.positions.iter() {
+            assert!(pos[0] < 100.0 && pos[1] < 100.0, "{:?} decoded outside the world", pos);
+        }
+        fluid_sim.particles = frame.to_particles();
+        fluid_sim.spatial_hash_particles();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_positions_outside_the_world() {
+        let path = temp_path("bad_position");
+        let fluid_sim = FluidSim::with_seed(20, 10, 0);
+        let mut writer = ParticleCacheWriter::create(&path, &fluid_sim, CacheOptions::new()).unwrap();
+        let mut frame = CacheFrame::from_particles(&vec![Particle::new(vec2(5.0, 5.0))], 0.0);
+        writer.write_cache_frame(&frame).unwrap();
+        frame.positions[0] = vec2(25.0, 5.0);
+        writer.write_cache_frame(&frame).unwrap();
+        frame.positions[0] = vec2(f32::NAN, 5.0);
+        writer.write_cache_frame(&frame).unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = ParticleCacheReader::open(&path).unwrap();
+        assert!(reader.read_frame(0).is_ok());
+        assert!(reader.read_frame(1).unwrap_err().contains("outside"));
+        assert!(reader.read_frame(2).unwrap_err().contains("outside"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_zero_frames_per_chunk() {
+        let path = write_cache("zero_chunk", 1);
+        let mut data = fs::read(&path).unwrap();
+        // frames per chunk is the last field of the header
+        data[29..33].copy_from_slice(&0u32.to_le_bytes());
+        fs::write(&path, &data).unwrap();
+        assert!(open_err(&path).contains("frames_per_chunk"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_index_past_the_end_of_the_file() {
+        let path = write_cache("bad_index", 3);
+        let mut data = fs::read(&path).unwrap();
+        // claim a huge frame count so the index would need far more bytes than the file has
+        let footer = data.len() - 16;
+        data[footer..footer + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, &data).unwrap();
+        assert!(open_err(&path).contains("corrupt index"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_frame_length_past_the_frame_data() {
+        let path = write_cache("bad_frame", 1);
+        let mut data = fs::read(&path).unwrap();
+        // the first frame length follows the 33 byte header
+        data[33..37].copy_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, &data).unwrap();
+        let mut reader = ParticleCacheReader::open(&path).unwrap();
+        assert!(reader.read_frame(0).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_materials_outside_the_cache() {
+        let path = write_cache("bad_material", 1);
+        let mut data = fs::read(&path).unwrap();
+        // the material of the first particle follows the frame length, time, count, positions and velocities
+        let material = 33 + 4 + 4 + 4 + 2 * 8 + 2 * 8;
+        data[material..material + 2].copy_from_slice(&7u16.to_le_bytes());
+        fs::write(&path, &data).unwrap();
+        let mut reader = ParticleCacheReader::open(&path).unwrap();
+        assert!(reader.read_frame(0).unwrap_err().contains("material 7"));
+        fs::remove_file(&path).unwrap();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,116 @@
+use core_simd::*;
+use serde::{Serialize, Deserialize};
+use crate::shape::Shape;
+use crate::particle::Particle;
+use crate::vector_2::*;
+use crate::fluid_sim::Properties;
+use crate::scene::SceneShape;
+
+// a closed polygon, the last point joins back to the first
+// can be concave but should not self intersect
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Polygon {
+    #[serde(with = "serde_vec2_list")]
+    pub points: Vec<f32x2>
+}
+
+impl Polygon {
+    pub fn new(points: Vec<f32x2>) -> Polygon {
+        Polygon {
+            points
+        }
+    }
+
+    pub fn closest_point(&self, pt: f32x2) -> f32x2 {
+        let mut closest = pt;
+        let mut closest_dist_sqrd = f32::MAX;
+        for i in 0..self.points.len() {
+            let a = self.points[i];
+            let b = self.points[(i + 1) % self.points.len()];
+            let edge_pt = closest_point_on_segment(pt, a, b);
+            let dist_sqrd = length_squared(pt - edge_pt);
+            if dist_sqrd < closest_dist_sqrd {
+                closest_dist_sqrd = dist_sqrd;
+                closest = edge_pt;
+            }
+        }
+        return closest;
+    }
+}
+
+impl Shape for Polygon {
+    fn collide_with(&self, particle: &mut Particle, properties: &Properties) {
+        if self.points.len() < 2 {
+            return;
+        }
+
+        let closest = self.closest_point(particle.pos);
+        let delta = particle.pos - closest;
+        let dist_sqrd = length_squared(delta);
+        if dist_sqrd <= 0.0 {
+            return;
+        }
+
+        let dist = dist_sqrd.sqrt();
+        let inside = self.contains(particle.pos);
+        if !inside && dist >= properties.radius {
+            return;
+        }
+
+        // the normal always points out of the polygon
+        let (normal, dist_to_push) = if inside {
+            (-delta / vec2_from_single(dist), dist + properties.radius)
+        } else {
+            (delta / vec2_from_single(dist), properties.radius - dist)
+        };
+
+        if dot(particle.vel, normal) < 0.0 {
+            particle.vel = reflect(particle.vel, normal);
+        }
+        particle.pos += normal * vec2_from_single(dist_to_push);
+    }
+
+    // https://wrf.ecse.rpi.edu/Research/Short_Notes/pnpoly.html
+    fn contains(&self, pt: f32x2) -> bool {
+        let mut inside = false;
+        let mut j = self.points.len().wrapping_sub(1);
+        for i in 0..self.points.len() {
+            let a = self.points[i];
+            let b = self.points[j];
+            if (a[1] > pt[1]) != (b[1] > pt[1]) && pt[0] < (b[0] - a[0]) * (pt[1] - a[1]) / (b[1] - a[1]) + a[0] {
+                inside = !inside;
+            }
+            j = i;
+        }
+        return inside;
+    }
+
+    fn aabb(&self) -> (f32x2, f32x2) {
+        let mut min = vec2_from_single(f32::MAX);
+        let mut max = vec2_from_single(f32::MIN);
+        for pt in self.points.iter() {
+            min = vec2(min[0].min(pt[0]), min[1].min(pt[1]));
+            max = vec2(max[0].max(pt[0]), max[1].max(pt[1]));
+        }
+        return (min, max);
+    }
+
+    fn signed_distance(&self, pt: f32x2) -> (f32, f32x2) {
+        let closest = self.closest_point(pt);
+        let delta = pt - closest;
+        let dist = length_squared(delta).sqrt();
+        if dist <= 0.0 {
+            return (0.0, vec2(0.0, -1.0));
+        }
+
+        let normal = delta / vec2_from_single(dist);
+        if self.contains(pt) {
+            return (-dist, -normal);
+        }
+        return (dist, normal);
+    }
+
+    fn scene_shape(&self) -> Option<SceneShape> {
+        return Some(SceneShape::Polygon(self.clone()));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use crate::material::MaterialId;
+
+// when particles of the two reactant materials touch they may turn into the products
+// eg. water + lava -> steam + stone
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Reaction {
+    pub reactants: (MaterialId, MaterialId),
+    pub products: (MaterialId, MaterialId), // what each reactant becomes, in the same order as the reactants
+    pub probability: f32, // chance per second of touching that the reaction happens
+    pub energy: f32, // temperature added to both products, negative absorbs heat
+}
+
+impl Reaction {
+    pub fn new(reactants: (MaterialId, MaterialId), products: (MaterialId, MaterialId), probability: f32) -> Reaction {
+        Reaction {
+            reactants,
+            products,
+            probability,
+            energy: 0.0
+        }
+    }
+
+    // the same reaction with the reactants (and products) swapped around
+    pub fn reversed(&self) -> Reaction {
+        Reaction {
+            reactants: (self.reactants.1, self.reactants.0),
+            products: (self.products.1, self.products.0),
+            probability: self.probability,
+            energy: self.energy
+        }
+    }
+}
+
+// reactions keyed by the pair of materials, only one reaction per pair
+pub struct ReactionTable {
+    pub reactions: HashMap<(MaterialId, MaterialId), Reaction>,
+}
+
+impl ReactionTable {
+    pub fn new() -> ReactionTable {
+        ReactionTable {
+            reactions: HashMap::new()
+        }
+    }
+
+    // stored both ways around so lookups don't care about the order of the pair
+    pub fn add(&mut self, reaction: Reaction) {
+        let reversed = reaction.reversed();
+        self.reactions.insert(reaction.reactants, reaction);
+        self.reactions.insert(reversed.reactants, reversed);
+    }
+
+    // the returned reaction has material a as its first reactant
+    pub fn get(&self, a: MaterialId, b: MaterialId) -> Option<&Reaction> {
+        return self.reactions.get(&(a, b));
+    }
+
+    // each reaction once, in a stable order
+    pub fn to_vec(&self) -> Vec<Reaction> {
+        let mut reactions: Vec<Reaction> = self.reactions.values().filter(|reaction| reaction.reactants.0 <= reaction.reactants.1).cloned().collect();
+        reactions.sort_by_key(|reaction| reaction.reactants);
+        return reactions;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.reactions.is_empty();
+    }
+}
\ No newline at end of file
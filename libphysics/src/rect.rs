@@ -1,13 +1,55 @@
 use core_simd::*;
+use serde::{Serialize, Deserialize};
 use crate::shape::Shape;
 use crate::particle::Particle;
 use crate::vector_2::*;
 use crate::fluid_sim::Properties;
+use crate::scene::SceneShape;
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Rect {
+    #[serde(with = "serde_vec2")]
     pub pos: f32x2,
+    #[serde(with = "serde_vec2")]
     pub size: f32x2,
-    pub rotation: f32 // radians
+    pub rotation: f32, // radians
+    #[serde(default)]
+    pub temperature: Option<f32> // None for shapes that don't exchange heat
+}
+
+// particles are treated as this size when colliding with rects
+const RADIUS: f32 = 1.0;
+
+impl Rect {
+    // the closest point within the rect to pos, and the vector from it to pos, both in the rect's unrotated space
+    fn closest_local(&self, pos: f32x2) -> (f32x2, f32x2) {
+        let local_pos = rotate_point_around(pos, self.pos, -self.rotation);
+        let half_size = self.size * Simd::from_array([0.5, 0.5]);
+
+        // Find the closest point to the circle within the rectangle
+        let closest = local_pos.clamp(self.pos - half_size, self.pos + half_size);
+
+        // Calculate the distance between the circle's center and this closest point
+        return (closest, local_pos - closest);
+    }
+
+    // where a particle at pos touches the rect and the normal collide_with pushes it along, in world space
+    // None if the particle isn't touching
+    pub fn collision_normal(&self, pos: f32x2) -> Option<(f32x2, f32x2)> {
+        let (closest, dist_vec) = self.closest_local(pos);
+        let dist_sqrd = length_squared(dist_vec);
+        if dist_sqrd >= RADIUS * RADIUS {
+            return None;
+        }
+
+        if dist_sqrd <= 0.0 {
+            let (dist, normal) = self.signed_distance(pos);
+            return Some((pos - normal * vec2_from_single(dist), normal));
+        }
+
+        let normal = dist_vec / vec2_from_single(dist_sqrd.sqrt());
+        return Some((rotate_point_around(closest, self.pos, self.rotation), rotate_vector(normal, self.rotation)));
+    }
 }
 
 // https://stackoverflow.com/questions/401847/circle-rectangle-collision-detection-intersection
@@ -18,20 +60,22 @@ pub struct Rect {
 // how to calculate how to push the particle out of the shape?
 impl Shape for Rect {
     fn collide_with(&self, circle: &mut Particle, _properties: &Properties) {
-        let circle_pos = rotate_point_around(circle.pos, self.pos, -self.rotation); 
-
-        const RADIUS: f32 = 1.0;
         let radius_sqrd = RADIUS * RADIUS;
 
-        let half_size = self.size * Simd::from_array([0.5, 0.5]);
-
-        // Find the closest point to the circle within the rectangle
-        let closest = circle_pos.clamp(self.pos - half_size, self.pos + half_size);
-
-        // Calculate the distance between the circle's center and this closest point
-        let dist_vec = circle_pos - closest;
+        let (_closest, dist_vec) = self.closest_local(circle.pos);
         let dist_sqrd: f32 = length_squared(dist_vec);
 
+        // a centre inside the rect, such as when a rect is dragged over particles, has no closest point to push away from
+        // so push it out through the nearest edge instead
+        if dist_sqrd <= 0.0 {
+            let (dist, normal) = self.signed_distance(circle.pos);
+            circle.pos += normal * vec2_from_single(RADIUS - dist);
+            if dot(circle.vel, normal) < 0.0 {
+                circle.vel = reflect(circle.vel, normal);
+            }
+            return;
+        }
+
         // If the distance is less than the circle's radius, an intersection occurs
         let collision = dist_sqrd < radius_sqrd;
         if collision {
@@ -55,4 +99,57 @@ impl Shape for Rect {
             //circle.pos += push_vec
         }
     }
+
+    fn contains(&self, pt: f32x2) -> bool {
+        // rotate the point into the rect's space so we can do an axis aligned test
+        let local_pt = rotate_point_around(pt, self.pos, -self.rotation);
+        let half_size = self.size * vec2_from_single(0.5);
+        let delta = local_pt - self.pos;
+        return delta[0].abs() <= half_size[0] && delta[1].abs() <= half_size[1];
+    }
+
+    fn aabb(&self) -> (f32x2, f32x2) {
+        let half_size = self.size * vec2_from_single(0.5);
+        let corners = [
+            vec2(-half_size[0], -half_size[1]),
+            vec2(half_size[0], -half_size[1]),
+            vec2(half_size[0], half_size[1]),
+            vec2(-half_size[0], half_size[1])
+        ];
+
+        let mut min = vec2_from_single(f32::MAX);
+        let mut max = vec2_from_single(f32::MIN);
+        for corner in corners {
+            let pt = self.pos + rotate_vector(corner, self.rotation);
+            min = vec2(min[0].min(pt[0]), min[1].min(pt[1]));
+            max = vec2(max[0].max(pt[0]), max[1].max(pt[1]));
+        }
+        return (min, max);
+    }
+
+    // https://iquilezles.org/articles/distfunctions2d/
+    fn signed_distance(&self, pt: f32x2) -> (f32, f32x2) {
+        let local_pt = rotate_point_around(pt, self.pos, -self.rotation) - self.pos;
+        let half_size = self.size * vec2_from_single(0.5);
+        let q = vec2(local_pt[0].abs() - half_size[0], local_pt[1].abs() - half_size[1]);
+        let sign = vec2(local_pt[0].signum(), local_pt[1].signum());
+
+        let (dist, normal) = if q[0] > 0.0 || q[1] > 0.0 {
+            // outside, the normal points from the closest point on the rect
+            let outside = vec2(q[0].max(0.0), q[1].max(0.0));
+            let dist = length_squared(outside).sqrt();
+            (dist, outside * sign / vec2_from_single(dist))
+        } else if q[0] > q[1] {
+            // inside and closest to a vertical edge
+            (q[0], vec2(sign[0], 0.0))
+        } else {
+            (q[1], vec2(0.0, sign[1]))
+        };
+
+        return (dist, rotate_vector(normal, self.rotation));
+    }
+
+    fn scene_shape(&self) -> Option<SceneShape> {
+        return Some(SceneShape::Rect(self.clone()));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,261 @@
+use std::fs;
+use std::path::Path;
+use core_simd::*;
+use serde::{Serialize, Deserialize};
+use crate::fluid_sim::{FluidSim, Properties};
+use crate::particle::Particle;
+use crate::material::{Material, MaterialId};
+use crate::reaction::Reaction;
+use crate::emitter::Emitter;
+use crate::shape::Shape;
+use crate::rect::Rect;
+use crate::circle::Circle;
+use crate::polygon::Polygon;
+use crate::lattice::Lattice;
+use crate::vector_2::*;
+
+fn zero() -> f32x2 {
+    return vec2_from_single(0.0);
+}
+
+fn lattice_default() -> Lattice {
+    return Lattice::Hex;
+}
+
+// the shapes that can be written to a scene file, used for sinks, sensors and particle blocks
+#[derive(Clone, Serialize, Deserialize)]
+pub enum SceneShape {
+    Rect(Rect),
+    Circle(Circle),
+    Polygon(Polygon),
+}
+
+impl SceneShape {
+    pub fn to_shape(&self) -> Box<dyn Shape> {
+        return match self {
+            SceneShape::Rect(rect) => Box::new(rect.clone()),
+            SceneShape::Circle(circle) => Box::new(circle.clone()),
+            SceneShape::Polygon(polygon) => Box::new(polygon.clone()),
+        };
+    }
+}
+
+// a shape filled with particles at rest spacing
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ParticleBlock {
+    pub shape: SceneShape,
+    #[serde(default = "lattice_default")]
+    pub lattice: Lattice,
+    #[serde(default)]
+    pub material: MaterialId,
+    #[serde(default = "zero", with = "serde_vec2")]
+    pub velocity: f32x2,
+    #[serde(default)]
+    pub temperature: Option<f32>, // None for the ambient temperature
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SceneParticle {
+    #[serde(with = "serde_vec2")]
+    pub pos: f32x2,
+    #[serde(default = "zero", with = "serde_vec2")]
+    pub vel: f32x2,
+    #[serde(default)]
+    pub material: MaterialId,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+}
+
+// everything needed to set up a FluidSim, read from RON, JSON or TOML depending on the file extension
+// only the world size is required, everything else has a default
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Scene {
+    pub x_size: usize,
+    pub y_size: usize,
+    #[serde(default)]
+    pub seed: Option<u64>, // None for a different random sim each run
+    #[serde(default)]
+    pub properties: Properties,
+    #[serde(default)]
+    pub materials: Vec<Material>, // replaces the default water material if not empty, material 0 is the default for particles
+    #[serde(default)]
+    pub reactions: Vec<Reaction>,
+    #[serde(default)]
+    pub rects: Vec<Rect>,
+    #[serde(default)]
+    pub emitters: Vec<Emitter>,
+    #[serde(default)]
+    pub sinks: Vec<SceneShape>,
+    #[serde(default)]
+    pub sensors: Vec<SceneShape>,
+    #[serde(default)]
+    pub blocks: Vec<ParticleBlock>,
+    #[serde(default)]
+    pub random_particles: usize, // number of particles scattered randomly over the world
+    #[serde(default)]
+    pub particles: Vec<SceneParticle>,
+}
+
+impl Scene {
+    pub fn new(x_size: usize, y_size: usize) -> Scene {
+        Scene {
+            x_size,
+            y_size,
+            seed: None,
+            properties: Properties::default(),
+            materials: vec![],
+            reactions: vec![],
+            rects: vec![],
+            emitters: vec![],
+            sinks: vec![],
+            sensors: vec![],
+            blocks: vec![],
+            random_particles: 0,
+            particles: vec![]
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Scene, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        let scene = match extension(path).as_str() {
+            "ron" => ron::from_str(&contents).map_err(|e| e.to_string()),
+            "json" => serde_json::from_str(&contents).map_err(|e| e.to_string()),
+            "toml" => toml::from_str(&contents).map_err(|e| e.to_string()),
+            ext => return Err(format!("unknown scene format '{}', expected ron, json or toml", ext))
+        };
+        return scene.map_err(|e| format!("failed to parse {}: {}", path.display(), e));
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = match extension(path).as_str() {
+            "ron" => ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(|e| e.to_string())?,
+            "json" => serde_json::to_string_pretty(self).map_err(|e| e.to_string())?,
+            "toml" => toml::to_string_pretty(self).map_err(|e| e.to_string())?,
+            ext => return Err(format!("unknown scene format '{}', expected ron, json or toml", ext))
+        };
+        return fs::write(path, contents).map_err(|e| format!("failed to write {}: {}", path.display(), e));
+    }
+
+    // the current state of a sim, particles are saved individually
+    // force fields and any sink or sensor shapes that can't be written to a scene are left out
+    pub fn from_fluid_sim(fluid_sim: &FluidSim) -> Scene {
+        let mut scene = Scene::new(fluid_sim.spatial_hash.x_size, fluid_sim.spatial_hash.y_size);
+        scene.properties = fluid_sim.properties.clone();
+        scene.materials = fluid_sim.materials.clone();
+        scene.reactions = fluid_sim.reactions.to_vec();
+        scene.rects = fluid_sim.rects.clone();
+        scene.emitters = fluid_sim.emitters.clone();
+        scene.sinks = fluid_sim.sinks.iter().filter_map(|sink| sink.shape.scene_shape()).collect();
+        scene.sensors = fluid_sim.sensors.iter().filter_map(|sensor| sensor.shape.scene_shape()).collect();
+        scene.particles = fluid_sim.particles.iter().map(|particle| SceneParticle {
+            pos: particle.pos,
+            vel: particle.vel,
+            material: particle.material,
+            temperature: Some(particle.temperature)
+        }).collect();
+        return scene;
+    }
+
+    pub fn to_fluid_sim(&self) -> Result<FluidSim, String> {
+        self.validate()?;
+
+        let mut fluid_sim = match self.seed {
+            Some(seed) => FluidSim::with_seed(self.x_size, self.y_size, seed),
+            None => FluidSim::new(self.x_size, self.y_size)
+        };
+
+        fluid_sim.properties = self.properties.clone();
+        let radius = fluid_sim.properties.radius;
+        fluid_sim.properties.dist_squared_max = (radius + radius) * (radius + radius);
+
+        // added after the properties as plastic materials size their grid from the radius
+        if !self.materials.is_empty() {
+            fluid_sim.materials.clear();
+            for material in self.materials.iter() {
+                fluid_sim.add_material(material.clone());
+            }
+        }
+
+        for reaction in self.reactions.iter() {
+            fluid_sim.add_reaction(*reaction);
+        }
+
+        fluid_sim.rects = self.rects.clone();
+        for emitter in self.emitters.iter() {
+            fluid_sim.add_emitter(emitter.clone());
+        }
+        for sink in self.sinks.iter() {
+            fluid_sim.add_sink(sink.to_shape());
+        }
+        for sensor in self.sensors.iter() {
+            fluid_sim.add_sensor(sensor.to_shape());
+        }
+
+        let mut particles: Vec<Particle> = Vec::new();
+        for block in self.blocks.iter() {
+            for mut particle in fluid_sim.generate_particles_in_shape(&*block.shape.to_shape(), block.lattice) {
+                particle.vel = block.velocity;
+                particle.material = block.material;
+                if let Some(temperature) = block.temperature {
+                    particle.temperature = temperature;
+                }
+                particles.push(particle);
+            }
+        }
+
+        particles.append(&mut fluid_sim.generate_random_particles(self.random_particles));
+
+        for scene_particle in self.particles.iter() {
+            let mut particle = Particle::with_vel(scene_particle.pos, scene_particle.vel);
+            particle.material = scene_particle.material;
+            if let Some(temperature) = scene_particle.temperature {
+                particle.temperature = temperature;
+            }
+            particles.push(particle);
+        }
+
+        fluid_sim.add_particles(&particles);
+        return Ok(fluid_sim);
+    }
+
+    // catch anything that would otherwise panic part way through a run
+    fn validate(&self) -> Result<(), String> {
+        if self.x_size == 0 || self.y_size == 0 {
+            return Err(format!("world size must not be zero, got {}x{}", self.x_size, self.y_size));
+        }
+
+        if self.properties.radius <= 0.0 {
+            return Err(format!("radius must be positive, got {}", self.properties.radius));
+        }
+
+        let material_count = self.materials.len().max(1);
+        let mut used: Vec<(&str, MaterialId)> = Vec::new();
+        used.extend(self.emitters.iter().map(|emitter| ("emitter", emitter.material)));
+        used.extend(self.blocks.iter().map(|block| ("block", block.material)));
+        used.extend(self.particles.iter().map(|particle| ("particle", particle.material)));
+        for reaction in self.reactions.iter() {
+            used.extend([reaction.reactants.0, reaction.reactants.1, reaction.products.0, reaction.products.1].iter().map(|material| ("reaction", *material)));
+        }
+        for material in self.materials.iter() {
+            used.extend(material.phase_above.iter().chain(material.phase_below.iter()).map(|phase| ("phase change", phase.material)));
+        }
+
+        for (what, material) in used {
+            if material >= material_count {
+                return Err(format!("{} uses material {} but there are only {} materials", what, material, material_count));
+            }
+        }
+
+        for particle in self.particles.iter() {
+            if particle.pos[0] < 0.0 || particle.pos[1] < 0.0 || particle.pos[0] as usize >= self.x_size || particle.pos[1] as usize >= self.y_size {
+                return Err(format!("particle at {:?} is outside the world", particle.pos.to_array()));
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+fn extension(path: &Path) -> String {
+    return path.extension().and_then(|ext| ext.to_str()).unwrap_or("").to_lowercase();
+}
\ No newline at end of file
@@ -0,0 +1,64 @@
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use crate::shape::Shape;
+use crate::particle::{Particle, ParticleId};
+use crate::fluid_sim::Properties;
+
+pub type SensorId = usize;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SensorEvent {
+    Entered { sensor: SensorId, particle: ParticleId },
+    Left { sensor: SensorId, particle: ParticleId }, // also sent when a particle inside the sensor is removed from the sim
+    Changed { sensor: SensorId, count: usize, volume: f32 }, // sent once per step when the number of particles inside changes
+}
+
+// a non-colliding region that tracks which particles are inside of it
+// so gameplay can tell when water reaches a drain or fills a bucket
+pub struct Sensor {
+    pub shape: Box<dyn Shape>,
+    pub inside: BTreeSet<ParticleId>, // BTreeSet so events are generated in a stable order
+    pub count: usize,
+    pub volume: f32,
+}
+
+impl Sensor {
+    pub fn new(shape: Box<dyn Shape>) -> Sensor {
+        Sensor {
+            shape,
+            inside: BTreeSet::new(),
+            count: 0,
+            volume: 0.0
+        }
+    }
+
+    pub fn update(&mut self, sensor: SensorId, particles: &Vec<Particle>, properties: &Properties, events: &mut VecDeque<SensorEvent>) {
+        // TODO: spatial hashing
+        let mut now_inside: BTreeSet<ParticleId> = BTreeSet::new();
+        for particle in particles.iter() {
+            if self.shape.contains(particle.pos) {
+                now_inside.insert(particle.id);
+            }
+        }
+
+        for particle in now_inside.difference(&self.inside) {
+            events.push_back(SensorEvent::Entered { sensor, particle: *particle });
+        }
+
+        for particle in self.inside.difference(&now_inside) {
+            events.push_back(SensorEvent::Left { sensor, particle: *particle });
+        }
+
+        let count = now_inside.len();
+        if count != self.count {
+            // each particle is treated as a disc of the particle radius
+            let particle_volume = PI * properties.radius * properties.radius;
+            self.count = count;
+            self.volume = count as f32 * particle_volume;
+            events.push_back(SensorEvent::Changed { sensor, count: self.count, volume: self.volume });
+        }
+
+        self.inside = now_inside;
+    }
+}
\ No newline at end of file
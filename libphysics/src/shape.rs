@@ -1,6 +1,16 @@
+use core_simd::*;
 use crate::particle::Particle;
 use crate::fluid_sim::Properties;
+use crate::scene::SceneShape;
 
 pub trait Shape {
     fn collide_with(&self, particle: &mut Particle, properties: &Properties);
+    fn contains(&self, pt: f32x2) -> bool;
+    fn aabb(&self) -> (f32x2, f32x2); // min, max
+    fn signed_distance(&self, pt: f32x2) -> (f32, f32x2); // distance to the surface (negative inside) and the outward normal
+
+    // None for shapes that can't be saved to a scene file
+    fn scene_shape(&self) -> Option<SceneShape> {
+        return None;
+    }
 }
\ No newline at end of file
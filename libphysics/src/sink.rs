@@ -0,0 +1,32 @@
+use crate::shape::Shape;
+use crate::particle::Particle;
+
+// removes any particle that ends up inside of it, used for drains
+pub struct Sink {
+    pub shape: Box<dyn Shape>,
+    pub enabled: bool,
+    pub removed: usize, // total particles removed so far
+}
+
+impl Sink {
+    pub fn new(shape: Box<dyn Shape>) -> Sink {
+        Sink {
+            shape,
+            enabled: true,
+            removed: 0
+        }
+    }
+
+    // returns true if any particles were removed
+    pub fn remove_particles(&mut self, particles: &mut Vec<Particle>) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        let count = particles.len();
+        particles.retain(|particle| !self.shape.contains(particle.pos));
+        let removed = count - particles.len();
+        self.removed += removed;
+        return removed > 0;
+    }
+}
\ No newline at end of file
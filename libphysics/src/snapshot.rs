@@ -0,0 +1,106 @@
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+use rand::rngs::StdRng;
+use crate::particle::{Particle, ParticleId};
+use crate::rect::Rect;
+use crate::emitter::Emitter;
+use crate::sensor::SensorEvent;
+use crate::cluster::Cluster;
+use crate::fluid_sim::Properties;
+
+// the parts of a sensor that change as the sim runs, the shape is setup so is not captured
+#[derive(Clone)]
+pub struct SensorState {
+    pub inside: BTreeSet<ParticleId>,
+    pub count: usize,
+    pub volume: f32
+}
+
+// everything that changes while the sim runs, so it can be put back exactly for undo or rollback
+// materials, force fields, reactions and the shapes of sensors and sinks are setup and are not captured,
+// so they must be the same when restoring
+#[derive(Clone)]
+pub struct Snapshot {
+    pub particles: Vec<Particle>, // without contacts, they point into the live particle list
+    pub rects: Vec<Rect>,
+    pub properties: Properties,
+    pub emitters: Vec<Emitter>,
+    pub sensors: Vec<SensorState>,
+    pub sensor_events: VecDeque<SensorEvent>,
+    pub sinks_removed: Vec<usize>,
+    pub clusters: Vec<Cluster>,
+    pub next_particle_id: ParticleId,
+    pub rng: StdRng,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fluid_sim::FluidSim;
+    use crate::flip::FlipSolver;
+    use crate::material::Material;
+    use crate::rect::Rect;
+    use crate::circle::Circle;
+    use crate::lattice::Lattice;
+    use crate::vector_2::*;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn block(pos: f32x2, size: f32x2) -> Rect {
+        return Rect { pos, size, rotation: 0.0, temperature: None };
+    }
+
+    // the same setup as physics/scenes/dam_break.ron, water released against a block of sand with a drain in the corner
+    fn dam_break() -> FluidSim {
+        let mut fluid_sim = FluidSim::with_seed(100, 60, 1);
+        fluid_sim.properties.gravity = vec2(0.0, 9.8);
+        let sand = fluid_sim.add_material(Material::sand());
+        fluid_sim.rects.push(block(vec2(50.0, 50.0), vec2(4.0, 20.0)));
+        fluid_sim.add_sink(Box::new(Circle { pos: vec2(96.0, 56.0), radius: 4.0 }));
+
+        let mut particles = fluid_sim.generate_particles_in_shape(&block(vec2(15.0, 40.0), vec2(24.0, 36.0)), Lattice::Hex);
+        for mut particle in fluid_sim.generate_particles_in_shape(&block(vec2(75.0, 52.0), vec2(20.0, 12.0)), Lattice::Square) {
+            particle.material = sand;
+            particles.push(particle);
+        }
+        fluid_sim.add_particles(&particles);
+        return fluid_sim;
+    }
+
+    fn step(fluid_sim: &mut FluidSim, steps: usize) {
+        for _ in 0..steps {
+            fluid_sim.update(DT);
+        }
+    }
+
+    fn state_bits(fluid_sim: &FluidSim) -> Vec<[u32; 4]> {
+        return fluid_sim.particles.iter().map(|p| [p.pos[0].to_bits(), p.pos[1].to_bits(), p.vel[0].to_bits(), p.vel[1].to_bits()]).collect();
+    }
+
+    fn assert_restore_replays_exactly(mut fluid_sim: FluidSim) {
+        step(&mut fluid_sim, 20);
+        let snapshot = fluid_sim.snapshot();
+
+        step(&mut fluid_sim, 30);
+        let expected = state_bits(&fluid_sim);
+
+        fluid_sim.restore(&snapshot).unwrap();
+        step(&mut fluid_sim, 30);
+
+        assert_eq!(state_bits(&fluid_sim), expected);
+    }
+
+    #[test]
+    fn restore_replays_mpm_and_sinks_exactly() {
+        let fluid_sim = dam_break();
+        assert!(fluid_sim.mpm.is_some());
+        assert_eq!(fluid_sim.sinks.len(), 1);
+        assert_restore_replays_exactly(fluid_sim);
+    }
+
+    #[test]
+    fn restore_replays_flip_exactly() {
+        let mut fluid_sim = dam_break();
+        fluid_sim.flip = Some(FlipSolver::new(&fluid_sim.spatial_hash, 1.0));
+        assert_restore_replays_exactly(fluid_sim);
+    }
+}
\ No newline at end of file
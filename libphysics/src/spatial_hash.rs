@@ -26,7 +26,7 @@ impl SpatialHash {
         let total_size = x_size * y_size;
         let empty_cell = Cell::new();
 
-        let size_mult: u32x2 = Simd::from_array([1, y_size as u32]);
+        let size_mult: u32x2 = Simd::from_array([1, x_size as u32]); // rows are x_size cells long
         SpatialHash{
             x_size,
             y_size,
@@ -62,4 +62,25 @@ impl SpatialHash {
             }
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fluid_sim::FluidSim;
+    use crate::particle::Particle;
+    use crate::vector_2::*;
+
+    // rows are x_size cells long, a world twice as wide as it is tall used to hash into the wrong cells
+    #[test]
+    fn non_square_world_finds_neighbours_in_the_next_row() {
+        let mut fluid_sim = FluidSim::with_seed(20, 10, 0);
+        fluid_sim.properties.gravity = vec2(0.0, 0.0);
+        fluid_sim.add_particles(&vec![Particle::new(vec2(19.5, 3.9)), Particle::new(vec2(19.5, 4.1)), Particle::new(vec2(2.5, 8.5))]);
+
+        fluid_sim.update(0.001);
+
+        assert_eq!(fluid_sim.particles[0].contacts.len(), 1);
+        assert_eq!(fluid_sim.particles[1].contacts.len(), 1);
+        assert_eq!(fluid_sim.particles[2].contacts.len(), 0);
+    }
 }
\ No newline at end of file
@@ -47,7 +47,7 @@ impl<'a> SpatialHashIter<'a> {
         x -= 1;
         let y = Wrapping(y_start);
 
-        let mut cell = Wrapping(x_start + (y.0 * spatial_hash.y_size));
+        let mut cell = Wrapping(x_start + (y.0 * spatial_hash.x_size));
         cell -= 1 as usize;
 
         let delta = x_end - x_start;
@@ -69,7 +69,7 @@ impl<'a> SpatialHashIter<'a> {
     pub fn next(&mut self) -> bool {
         self.x += Wrapping(1);
         self.cell += Wrapping(1);
-        //assert!(self.cell == Wrapping(self.x.0 + (self.y.0 * self.spatial_hash.y_size)), "Ops! Stride must be wrong");
+        //assert!(self.cell == Wrapping(self.x.0 + (self.y.0 * self.spatial_hash.x_size)), "Ops! Stride must be wrong");
 
         if self.x >= Wrapping(self.x_end) {
             self.y += Wrapping(1);
@@ -81,7 +81,7 @@ impl<'a> SpatialHashIter<'a> {
 
             self.x = Wrapping(self.x_start);
 
-            //assert!(self.cell == Wrapping(self.x.0 + (self.y.0 * self.spatial_hash.y_size)), "Ops! Stride must be wrong");
+            //assert!(self.cell == Wrapping(self.x.0 + (self.y.0 * self.spatial_hash.x_size)), "Ops! Stride must be wrong");
         }
         return true;
     }
@@ -5,7 +5,7 @@ pub fn test() {
     const GRID_SIZE: usize = 300;
     const PARTICLE_COUNT: usize = 2000;
 
-    let mut fs = FluidSim::new(GRID_SIZE, GRID_SIZE);
+    let mut fs = FluidSim::with_seed(GRID_SIZE, GRID_SIZE, 0);
     let particles = fs.generate_random_particles(PARTICLE_COUNT);
     fs.add_particles(&particles);
 
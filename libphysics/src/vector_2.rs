@@ -65,3 +65,48 @@ pub fn rotate_point_around(pt: f32x2, origin: f32x2, angle_rad: f32) -> f32x2 {
     let new_pt = origin + rotated;
     return new_pt;
 }
+
+// closest point to pt on the line segment a -> b
+#[inline(always)]
+pub fn closest_point_on_segment(pt: f32x2, a: f32x2, b: f32x2) -> f32x2 {
+    let ab = b - a;
+    let len_sqrd = length_squared(ab);
+    if len_sqrd <= 0.0 {
+        return a;
+    }
+    let t = (dot(pt - a, ab) / len_sqrd).max(0.0).min(1.0);
+    return a + ab * vec2_from_single(t);
+}
+
+
+// serde helpers for f32x2, stored as [x, y]
+// use with #[serde(with = "serde_vec2")]
+pub mod serde_vec2 {
+    use core_simd::*;
+    use serde::{Serialize, Deserialize, Serializer, Deserializer};
+
+    pub fn serialize<S: Serializer>(v: &f32x2, serializer: S) -> Result<S::Ok, S::Error> {
+        return v.to_array().serialize(serializer);
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32x2, D::Error> {
+        let v = <[f32; 2]>::deserialize(deserializer)?;
+        return Ok(Simd::from_array(v));
+    }
+}
+
+// as serde_vec2 but for a Vec<f32x2>
+pub mod serde_vec2_list {
+    use core_simd::*;
+    use serde::{Serialize, Deserialize, Serializer, Deserializer};
+
+    pub fn serialize<S: Serializer>(list: &Vec<f32x2>, serializer: S) -> Result<S::Ok, S::Error> {
+        let list: Vec<[f32; 2]> = list.iter().map(|v| v.to_array()).collect();
+        return list.serialize(serializer);
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<f32x2>, D::Error> {
+        let list = Vec::<[f32; 2]>::deserialize(deserializer)?;
+        return Ok(list.into_iter().map(|v| Simd::from_array(v)).collect());
+    }
+}
\ No newline at end of file
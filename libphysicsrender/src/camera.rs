@@ -0,0 +1,158 @@
+use libphysics::FluidSim;
+use libphysics::*;
+
+use crate::fluid_sim_renderer::PADDING;
+
+// how the world is scaled to the viewport before zooming
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ViewportFit {
+    Height, // the world height fills the viewport
+    Width, // the world width fills the viewport
+    Contain, // the whole world is visible
+}
+
+impl ViewportFit {
+    // cycles through the fits
+    pub fn next(&self) -> ViewportFit {
+        return match self {
+            ViewportFit::Height => ViewportFit::Width,
+            ViewportFit::Width => ViewportFit::Contain,
+            ViewportFit::Contain => ViewportFit::Height
+        };
+    }
+}
+
+// maps between world and screen space, viewports are in pixels
+// the default camera shows the world from the top left corner inside the padding, fitted to the viewport height
+pub struct Camera {
+    pub pos: f32x2, // world position shown at the top left corner inside the padding
+    pub zoom: f32, // 1 shows the world as fitted
+    pub fit: ViewportFit,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+}
+
+impl Camera {
+    pub fn new() -> Camera {
+        Camera {
+            pos: vec2(0.0, 0.0),
+            zoom: 1.0,
+            fit: ViewportFit::Height,
+            min_zoom: 0.1,
+            max_zoom: 50.0
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.pos = vec2(0.0, 0.0);
+        self.zoom = 1.0;
+    }
+
+    // pixels per world unit
+    pub fn scale(&self, fluid_sim: &FluidSim, viewport: (u32, u32)) -> f32 {
+        let height_scale = ((viewport.1 as f32) - (PADDING * 2.0)) / (fluid_sim.spatial_hash.y_size as f32);
+        let width_scale = ((viewport.0 as f32) - (PADDING * 2.0)) / (fluid_sim.spatial_hash.x_size as f32);
+        let fit_scale = match self.fit {
+            ViewportFit::Height => height_scale,
+            ViewportFit::Width => width_scale,
+            ViewportFit::Contain => height_scale.min(width_scale)
+        };
+        return fit_scale * self.zoom;
+    }
+
+    // scale and offset so that screen = world * scale + offset
+    pub fn transform(&self, fluid_sim: &FluidSim, viewport: (u32, u32)) -> (f32, f32x2) {
+        let scale = self.scale(fluid_sim, viewport);
+        let offset = vec2(PADDING, PADDING) - self.pos * vec2_from_single(scale);
+        return (scale, offset);
+    }
+
+    pub fn world_to_screen(&self, fluid_sim: &FluidSim, viewport: (u32, u32), world: f32x2) -> f32x2 {
+        let (scale, offset) = self.transform(fluid_sim, viewport);
+        return world * vec2_from_single(scale) + offset;
+    }
+
+    pub fn screen_to_world(&self, fluid_sim: &FluidSim, viewport: (u32, u32), screen: f32x2) -> f32x2 {
+        let (scale, offset) = self.transform(fluid_sim, viewport);
+        return (screen - offset) / vec2_from_single(scale);
+    }
+
+    // move the view by a screen space delta, such as a mouse drag
+    pub fn pan(&mut self, fluid_sim: &FluidSim, viewport: (u32, u32), screen_delta: f32x2) {
+        let scale = self.scale(fluid_sim, viewport);
+        self.pos -= screen_delta / vec2_from_single(scale);
+    }
+
+    // zoom by a factor keeping the world position under screen_pos fixed, such as the mouse cursor
+    pub fn zoom_at(&mut self, fluid_sim: &FluidSim, viewport: (u32, u32), screen_pos: f32x2, factor: f32) {
+        let world = self.screen_to_world(fluid_sim, viewport, screen_pos);
+        self.zoom = (self.zoom * factor).max(self.min_zoom).min(self.max_zoom);
+        let scale = self.scale(fluid_sim, viewport);
+        self.pos = world - (screen_pos - vec2(PADDING, PADDING)) / vec2_from_single(scale);
+    }
+
+    // put a world position in the middle of the viewport, call each frame to follow something
+    pub fn centre_on(&mut self, fluid_sim: &FluidSim, viewport: (u32, u32), world: f32x2) {
+        let scale = self.scale(fluid_sim, viewport);
+        let centre = vec2(viewport.0 as f32, viewport.1 as f32) * vec2_from_single(0.5);
+        self.pos = world - (centre - vec2(PADDING, PADDING)) / vec2_from_single(scale);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libphysics::*;
+    use crate::camera::{Camera, ViewportFit};
+
+    const VIEWPORT: (u32, u32) = (800, 600);
+
+    fn assert_close(a: f32x2, b: f32x2) {
+        assert!((a[0] - b[0]).abs() < 1e-3 && (a[1] - b[1]).abs() < 1e-3, "{:?} != {:?}", a, b);
+    }
+
+    fn moved_camera() -> Camera {
+        let mut camera = Camera::new();
+        camera.pos = vec2(12.0, -3.5);
+        camera.zoom = 2.5;
+        return camera;
+    }
+
+    #[test]
+    fn screen_to_world_undoes_world_to_screen() {
+        let fluid_sim = FluidSim::with_seed(100, 60, 0);
+        for fit in [ViewportFit::Height, ViewportFit::Width, ViewportFit::Contain] {
+            let mut camera = moved_camera();
+            camera.fit = fit;
+            for world in [vec2(0.0, 0.0), vec2(37.5, 12.25), vec2(100.0, 60.0)] {
+                let screen = camera.world_to_screen(&fluid_sim, VIEWPORT, world);
+                assert_close(camera.screen_to_world(&fluid_sim, VIEWPORT, screen), world);
+            }
+        }
+    }
+
+    #[test]
+    fn zoom_at_keeps_the_point_under_the_cursor() {
+        let fluid_sim = FluidSim::with_seed(100, 60, 0);
+        let mut camera = moved_camera();
+        let cursor = vec2(500.0, 150.0);
+        let world = camera.screen_to_world(&fluid_sim, VIEWPORT, cursor);
+
+        for factor in [1.25, 0.5, 3.0] {
+            camera.zoom_at(&fluid_sim, VIEWPORT, cursor, factor);
+            assert_close(camera.world_to_screen(&fluid_sim, VIEWPORT, world), cursor);
+        }
+
+        // clamped to the zoom limits, the cursor still stays put
+        camera.zoom_at(&fluid_sim, VIEWPORT, cursor, 1000.0);
+        assert_eq!(camera.zoom, camera.max_zoom);
+        assert_close(camera.world_to_screen(&fluid_sim, VIEWPORT, world), cursor);
+    }
+
+    #[test]
+    fn centre_on_puts_the_point_in_the_middle() {
+        let fluid_sim = FluidSim::with_seed(100, 60, 0);
+        let mut camera = moved_camera();
+        camera.centre_on(&fluid_sim, VIEWPORT, vec2(70.0, 20.0));
+        assert_close(camera.world_to_screen(&fluid_sim, VIEWPORT, vec2(70.0, 20.0)), vec2(400.0, 300.0));
+    }
+}
\ No newline at end of file
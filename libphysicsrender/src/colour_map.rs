@@ -0,0 +1,234 @@
+use libphysics::FluidSim;
+use libphysics::*;
+
+use crate::software_renderer::Rgba;
+
+// what particles are coloured by
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColourMode {
+    Solid, // everything the same colour
+    Speed,
+    Contacts, // number of neighbouring particles touching
+    Density, // kernel weighted density, a stand in for pressure
+    Material,
+    Temperature,
+    Surface, // surface particles vs interior particles
+}
+
+impl ColourMode {
+    pub const ALL: [ColourMode; 7] = [ColourMode::Solid, ColourMode::Speed, ColourMode::Contacts, ColourMode::Density, ColourMode::Material, ColourMode::Temperature, ColourMode::Surface];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            ColourMode::Solid => "solid",
+            ColourMode::Speed => "speed",
+            ColourMode::Contacts => "contacts",
+            ColourMode::Density => "density",
+            ColourMode::Material => "material",
+            ColourMode::Temperature => "temperature",
+            ColourMode::Surface => "surface"
+        };
+    }
+
+    pub fn from_name(name: &str) -> Option<ColourMode> {
+        return ColourMode::ALL.iter().find(|mode| mode.name() == name).copied();
+    }
+
+    // cycles through the modes
+    pub fn next(&self) -> ColourMode {
+        let i = ColourMode::ALL.iter().position(|mode| mode == self).unwrap_or(0);
+        return ColourMode::ALL[(i + 1) % ColourMode::ALL.len()];
+    }
+}
+
+// colours at positions from 0 to 1, linearly interpolated between
+#[derive(Clone, Debug)]
+pub struct ColourRamp {
+    pub stops: Vec<(f32, Rgba)>, // sorted by position
+}
+
+impl ColourRamp {
+    pub fn new(stops: Vec<(f32, Rgba)>) -> ColourRamp {
+        ColourRamp {
+            stops
+        }
+    }
+
+    // black body, good for speed and energy
+    pub fn heat() -> ColourRamp {
+        return ColourRamp::new(vec![(0.0, [40, 0, 0, 255]), (0.35, [220, 30, 0, 255]), (0.7, [255, 200, 0, 255]), (1.0, [255, 255, 255, 255])]);
+    }
+
+    // perceptually even, good for counts and densities
+    pub fn viridis() -> ColourRamp {
+        return ColourRamp::new(vec![(0.0, [68, 1, 84, 255]), (0.25, [59, 82, 139, 255]), (0.5, [33, 145, 140, 255]), (0.75, [94, 201, 98, 255]), (1.0, [253, 231, 37, 255])]);
+    }
+
+    // blue for cold through white to red for hot
+    pub fn cool_warm() -> ColourRamp {
+        return ColourRamp::new(vec![(0.0, [60, 80, 220, 255]), (0.5, [220, 220, 220, 255]), (1.0, [200, 40, 40, 255])]);
+    }
+
+    pub fn sample(&self, t: f32) -> Rgba {
+        if self.stops.is_empty() {
+            return [255, 255, 255, 255];
+        }
+
+        let t = if t.is_nan() { 0.0 } else { t };
+        let first = self.stops[0];
+        if t <= first.0 {
+            return first.1;
+        }
+
+        for i in 1..self.stops.len() {
+            let (a_pos, a) = self.stops[i - 1];
+            let (b_pos, b) = self.stops[i];
+            if t <= b_pos {
+                let f = if b_pos > a_pos { (t - a_pos) / (b_pos - a_pos) } else { 1.0 };
+                let mut colour: Rgba = [0; 4];
+                for c in 0..4 {
+                    colour[c] = (a[c] as f32 + (b[c] as f32 - a[c] as f32) * f) as u8;
+                }
+                return colour;
+            }
+        }
+        return self.stops[self.stops.len() - 1].1;
+    }
+}
+
+// what the renderer needs to draw a key for the current mode
+pub enum Legend {
+    Ramp { title: String, ramp: ColourRamp, min: f32, max: f32 },
+    Swatches { title: String, entries: Vec<(String, Rgba)> },
+}
+
+// picks a particle colour from one of its attributes
+pub struct ColourMap {
+    pub mode: ColourMode,
+    pub solid: Rgba,
+    pub ramp: ColourRamp, // for speed, contacts and density
+    pub temperature_ramp: ColourRamp,
+    pub speed_range: (f32, f32),
+    pub contacts_range: (f32, f32),
+    pub density_range: (f32, f32),
+    pub temperature_range: (f32, f32),
+    pub material_colours: Vec<Rgba>, // by material id, repeats if there are more materials
+    pub surface: Rgba,
+    pub interior: Rgba,
+    pub surface_normal: f32, // surface normal length above which a particle is on the surface
+    pub show_legend: bool,
+}
+
+impl ColourMap {
+    pub fn new() -> ColourMap {
+        ColourMap {
+            mode: ColourMode::Solid,
+            solid: [0, 255, 0, 255],
+            ramp: ColourRamp::heat(),
+            temperature_ramp: ColourRamp::cool_warm(),
+            speed_range: (0.0, 20.0),
+            contacts_range: (0.0, 6.0),
+            density_range: (0.3, 1.0),
+            temperature_range: (0.0, 100.0),
+            material_colours: vec![[0, 255, 0, 255], [230, 190, 90, 255], [240, 240, 255, 255], [80, 160, 255, 255], [255, 90, 60, 255], [200, 100, 255, 255]],
+            surface: [80, 200, 255, 255],
+            interior: [0, 90, 200, 255],
+            surface_normal: 0.25,
+            show_legend: true
+        }
+    }
+
+    // particles with one neighbour or less have no inside, otherwise use the surface normal which is zero inside the fluid
+    pub fn is_surface(&self, particle: &Particle) -> bool {
+        return particle.contacts.len() <= 1 || length_squared(particle.normal) >= self.surface_normal * self.surface_normal;
+    }
+
+    pub fn colour(&self, particle: &Particle) -> Rgba {
+        return match self.mode {
+            ColourMode::Solid => self.solid,
+            ColourMode::Speed => self.ramp.sample(normalise(length_squared(particle.vel).sqrt(), self.speed_range)),
+            ColourMode::Contacts => self.ramp.sample(normalise(particle.contacts.len() as f32, self.contacts_range)),
+            ColourMode::Density => self.ramp.sample(normalise(particle.density, self.density_range)),
+            ColourMode::Material => self.material_colour(particle.material),
+            ColourMode::Temperature => self.temperature_ramp.sample(normalise(particle.temperature, self.temperature_range)),
+            ColourMode::Surface => if self.is_surface(particle) { self.surface } else { self.interior }
+        };
+    }
+
+    pub fn material_colour(&self, material: MaterialId) -> Rgba {
+        if self.material_colours.is_empty() {
+            return self.solid;
+        }
+        return self.material_colours[material % self.material_colours.len()];
+    }
+
+    // None when there is nothing worth explaining
+    pub fn legend(&self, fluid_sim: &FluidSim) -> Option<Legend> {
+        if !self.show_legend {
+            return None;
+        }
+
+        let ramp = |title: &str, ramp: &ColourRamp, range: (f32, f32)| Some(Legend::Ramp { title: title.to_string(), ramp: ramp.clone(), min: range.0, max: range.1 });
+        return match self.mode {
+            ColourMode::Solid => None,
+            ColourMode::Speed => ramp("speed", &self.ramp, self.speed_range),
+            ColourMode::Contacts => ramp("contacts", &self.ramp, self.contacts_range),
+            ColourMode::Density => ramp("density", &self.ramp, self.density_range),
+            ColourMode::Temperature => ramp("temperature", &self.temperature_ramp, self.temperature_range),
+            ColourMode::Material => Some(Legend::Swatches {
+                title: "material".to_string(),
+                entries: fluid_sim.materials.iter().enumerate().map(|(i, material)| (material.name.clone(), self.material_colour(i))).collect()
+            }),
+            ColourMode::Surface => Some(Legend::Swatches {
+                title: "surface".to_string(),
+                entries: vec![("surface".to_string(), self.surface), ("interior".to_string(), self.interior)]
+            })
+        };
+    }
+}
+
+fn normalise(value: f32, range: (f32, f32)) -> f32 {
+    if range.1 <= range.0 {
+        return 0.0;
+    }
+    return (value - range.0) / (range.1 - range.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::colour_map::{ColourMode, ColourRamp};
+
+    #[test]
+    fn ramp_returns_the_stop_colours_at_the_stops() {
+        for ramp in [ColourRamp::heat(), ColourRamp::viridis(), ColourRamp::cool_warm()] {
+            for (pos, colour) in ramp.stops.iter() {
+                assert_eq!(ramp.sample(*pos), *colour);
+            }
+        }
+    }
+
+    #[test]
+    fn ramp_interpolates_between_stops_and_clamps_outside() {
+        let ramp = ColourRamp::new(vec![(0.0, [0, 0, 0, 255]), (0.5, [200, 100, 0, 255]), (1.0, [200, 100, 200, 255])]);
+        assert_eq!(ramp.sample(0.25), [100, 50, 0, 255]);
+        assert_eq!(ramp.sample(0.75), [200, 100, 100, 255]);
+        assert_eq!(ramp.sample(-1.0), [0, 0, 0, 255]);
+        assert_eq!(ramp.sample(2.0), [200, 100, 200, 255]);
+        assert_eq!(ramp.sample(f32::INFINITY), [200, 100, 200, 255]);
+    }
+
+    #[test]
+    fn ramp_treats_nan_as_the_first_stop() {
+        let ramp = ColourRamp::heat();
+        assert_eq!(ramp.sample(f32::NAN), ramp.stops[0].1);
+        assert_eq!(ColourRamp::new(vec![]).sample(f32::NAN), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn mode_names_round_trip() {
+        for mode in ColourMode::ALL {
+            assert_eq!(ColourMode::from_name(mode.name()), Some(mode));
+        }
+        assert_eq!(ColourMode::from_name("plaid"), None);
+    }
+}
\ No newline at end of file
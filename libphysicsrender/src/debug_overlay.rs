@@ -0,0 +1,179 @@
+use libphysics::FluidSim;
+use libphysics::*;
+
+use crate::software_renderer::Rgba;
+
+// something for a renderer to draw, in screen space
+pub enum DebugShape {
+    Line(f32x2, f32x2, Rgba),
+    Rect(f32x2, f32x2, Rgba), // outline from min to max
+    Text(f32x2, String, Rgba), // top left of the text, renderers without a font skip it
+}
+
+// extra information drawn on top of the sim to see what it is doing, each layer can be switched on and off
+pub struct DebugOverlay {
+    pub enabled: bool,
+    pub hash_cells: bool, // occupied spatial hash cells with their particle counts
+    pub contacts: bool, // lines along the contact normals to each neighbour
+    pub velocities: bool,
+    pub aabbs: bool, // bounding boxes of rects, sinks and sensors
+    pub collision_normals: bool, // where particles touch rects and the normal they are pushed along
+    pub velocity_scale: f32, // seconds of travel shown by the velocity lines
+    pub min_text_cell: f32, // smallest hash cell in pixels that the counts are drawn in
+}
+
+const HASH_CELL: Rgba = [255, 0, 0, 100];
+const CONTACT: Rgba = [255, 255, 0, 160];
+const VELOCITY: Rgba = [0, 255, 255, 255];
+const AABB: Rgba = [255, 0, 255, 255];
+const COLLISION_NORMAL: Rgba = [255, 140, 0, 255];
+const TEXT: Rgba = [255, 255, 255, 200];
+
+impl DebugOverlay {
+    pub fn new() -> DebugOverlay {
+        DebugOverlay {
+            enabled: false,
+            hash_cells: true,
+            contacts: true,
+            velocities: true,
+            aabbs: true,
+            collision_normals: true,
+            velocity_scale: 0.1,
+            min_text_cell: 16.0
+        }
+    }
+
+    pub fn shapes(&self, fluid_sim: &FluidSim, scale: f32, offset: f32x2) -> Vec<DebugShape> {
+        let mut shapes: Vec<DebugShape> = vec![];
+        if !self.enabled {
+            return shapes;
+        }
+
+        let to_screen = |p: f32x2| p * vec2_from_single(scale) + offset;
+
+        // hash cells are 1 world unit square
+        if self.hash_cells {
+            let spatial_hash = &fluid_sim.spatial_hash;
+            for y in 0..spatial_hash.y_size {
+                for x in 0..spatial_hash.x_size {
+                    let count = spatial_hash.cells[x + y * spatial_hash.x_size].len();
+                    if count == 0 {
+                        continue;
+                    }
+
+                    let min = to_screen(vec2(x as f32, y as f32));
+                    let max = to_screen(vec2(x as f32 + 1.0, y as f32 + 1.0));
+                    shapes.push(DebugShape::Rect(min, max, HASH_CELL));
+                    if scale >= self.min_text_cell {
+                        shapes.push(DebugShape::Text(min + vec2(2.0, 2.0), count.to_string(), TEXT));
+                    }
+                }
+            }
+        }
+
+        if self.aabbs {
+            let rects = fluid_sim.rects.iter().map(|rect| rect.aabb());
+            let sinks = fluid_sim.sinks.iter().map(|sink| sink.shape.aabb());
+            let sensors = fluid_sim.sensors.iter().map(|sensor| sensor.shape.aabb());
+            for (min, max) in rects.chain(sinks).chain(sensors) {
+                shapes.push(DebugShape::Rect(to_screen(min), to_screen(max), AABB));
+            }
+        }
+
+        for particle in fluid_sim.particles.iter() {
+            let pos = to_screen(particle.pos);
+
+            // each particle draws half way to its neighbour, so a pair makes one line
+            // the neighbour is found from the normal and distance as the contact pointers may be stale
+            if self.contacts {
+                for contact in particle.contacts.iter() {
+                    let end = particle.pos + contact.normal * vec2_from_single(contact.depth * 0.5);
+                    shapes.push(DebugShape::Line(pos, to_screen(end), CONTACT));
+                }
+            }
+
+            if self.velocities {
+                let end = particle.pos + particle.vel * vec2_from_single(self.velocity_scale);
+                shapes.push(DebugShape::Line(pos, to_screen(end), VELOCITY));
+            }
+
+            if self.collision_normals {
+                for rect in fluid_sim.rects.iter() {
+                    if let Some((point, normal)) = rect.collision_normal(particle.pos) {
+                        let end = point + normal * vec2_from_single(fluid_sim.properties.radius);
+                        shapes.push(DebugShape::Line(to_screen(point), to_screen(end), COLLISION_NORMAL));
+                    }
+                }
+            }
+        }
+
+        return shapes;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libphysics::*;
+    use crate::debug_overlay::{DebugOverlay, DebugShape};
+
+    fn only(layer: &str) -> DebugOverlay {
+        let mut debug_overlay = DebugOverlay::new();
+        debug_overlay.enabled = true;
+        debug_overlay.hash_cells = layer == "hash_cells";
+        debug_overlay.contacts = layer == "contacts";
+        debug_overlay.velocities = layer == "velocities";
+        debug_overlay.aabbs = layer == "aabbs";
+        debug_overlay.collision_normals = layer == "collision_normals";
+        return debug_overlay;
+    }
+
+    fn lines(shapes: &Vec<DebugShape>) -> Vec<(f32x2, f32x2)> {
+        return shapes.iter().filter_map(|shape| match shape {
+            DebugShape::Line(a, b, _) => Some((*a, *b)),
+            _ => None
+        }).collect();
+    }
+
+    #[test]
+    fn draws_nothing_when_disabled() {
+        let mut fluid_sim = FluidSim::with_seed(10, 10, 0);
+        fluid_sim.add_particles(&vec![Particle::with_vel(vec2(3.5, 2.5), vec2(1.0, 0.0))]);
+        assert!(DebugOverlay::new().shapes(&fluid_sim, 10.0, vec2(0.0, 0.0)).is_empty());
+    }
+
+    #[test]
+    fn outlines_occupied_hash_cells_with_counts_when_big_enough() {
+        let mut fluid_sim = FluidSim::with_seed(10, 10, 0);
+        fluid_sim.add_particles(&vec![Particle::new(vec2(3.5, 2.5)), Particle::new(vec2(3.2, 2.8))]);
+        fluid_sim.spatial_hash_particles();
+        let debug_overlay = only("hash_cells");
+
+        let shapes = debug_overlay.shapes(&fluid_sim, 20.0, vec2(5.0, 5.0));
+        assert_eq!(shapes.len(), 2);
+        assert!(matches!(shapes[0], DebugShape::Rect(min, max, _) if min == vec2(65.0, 45.0) && max == vec2(85.0, 65.0)));
+        assert!(matches!(&shapes[1], DebugShape::Text(_, text, _) if text == "2"));
+
+        // too small to fit the count
+        let shapes = debug_overlay.shapes(&fluid_sim, 10.0, vec2(5.0, 5.0));
+        assert_eq!(shapes.len(), 1);
+    }
+
+    #[test]
+    fn velocity_and_contact_lines_are_in_screen_space() {
+        let mut fluid_sim = FluidSim::with_seed(10, 10, 0);
+        fluid_sim.properties.gravity = vec2(0.0, 0.0);
+        fluid_sim.add_particles(&vec![Particle::with_vel(vec2(3.0, 2.0), vec2(10.0, 0.0))]);
+        assert_eq!(lines(&only("velocities").shapes(&fluid_sim, 10.0, vec2(5.0, 5.0))), vec![(vec2(35.0, 25.0), vec2(45.0, 25.0))]);
+
+        // a touching pair each draw half way, so the lines meet in the middle
+        let mut fluid_sim = FluidSim::with_seed(10, 10, 0);
+        fluid_sim.properties.gravity = vec2(0.0, 0.0);
+        fluid_sim.add_particles(&vec![Particle::new(vec2(4.0, 5.0)), Particle::new(vec2(5.0, 5.0))]);
+        fluid_sim.update(0.001);
+        let contact_lines = lines(&only("contacts").shapes(&fluid_sim, 1.0, vec2(0.0, 0.0)));
+        assert_eq!(contact_lines.len(), 2);
+        let (a, b) = (contact_lines[0], contact_lines[1]);
+        assert!(length_squared(a.1 - b.1).sqrt() < 1e-3);
+        assert!(length_squared(a.0 - b.0).sqrt() > 0.9);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,62 @@
+use libphysics::*;
+
+// something that can draw a FluidSim, either to a window or an image
+pub trait FluidSimRenderer {
+    fn draw(&mut self, fluid_sim: &FluidSim);
+}
+
+// how liquid particles are drawn
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderMode {
+    Particles, // an outline circle per particle
+    Metaballs, // a smooth surface from the particle density, see MetaballStyle
+    Surface, // filled triangles and outlines from marching squares, see libphysics::Surface
+}
+
+impl RenderMode {
+    // cycles through the modes
+    pub fn toggled(&self) -> RenderMode {
+        return match self {
+            RenderMode::Particles => RenderMode::Metaballs,
+            RenderMode::Metaballs => RenderMode::Surface,
+            RenderMode::Surface => RenderMode::Particles
+        };
+    }
+}
+
+// padding around the world in pixels
+pub const PADDING: f32 = 20.0;
+
+// the corners of a rotated rect in screen space, in drawing order
+pub fn rect_corners(rect: &libphysics::Rect, scale: f32, offset: f32x2) -> [f32x2; 4] {
+    let half_size = rect.size * vec2_from_single(0.5) * vec2_from_single(scale);
+
+    let pos = (rect.pos * vec2_from_single(scale)) + offset;
+    let top_left = pos - half_size;
+    let bottom_right = pos + half_size;
+    let top_right = vec2(bottom_right[0], top_left[1]);
+    let bottom_left = vec2(top_left[0], bottom_right[1]);
+
+    let radians = rect.rotation;
+
+    // rotate points around origin
+    return [
+        rotate_point_around(top_left, pos, radians),
+        rotate_point_around(top_right, pos, radians),
+        rotate_point_around(bottom_right, pos, radians),
+        rotate_point_around(bottom_left, pos, radians)
+    ];
+}
+
+// colours for RenderMode::Surface
+pub const SURFACE_FILL: [u8; 4] = [40, 110, 220, 255];
+pub const SURFACE_OUTLINE: [u8; 4] = [150, 200, 255, 255];
+
+// legend layout in pixels, in the top right corner
+pub const LEGEND_WIDTH: i32 = 120;
+pub const LEGEND_ROW: i32 = 14;
+pub const LEGEND_SWATCH: i32 = 10;
+
+pub fn legend_origin(target_width: u32) -> (i32, i32) {
+    return (target_width as i32 - LEGEND_WIDTH - PADDING as i32 - 10, PADDING as i32 + 10);
+}
\ No newline at end of file
@@ -0,0 +1,140 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+
+// writes an RGBA image to a png file
+pub fn write_png(path: &Path, width: u32, height: u32, rgba: &[u8]) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("failed to create {}: {}", path.display(), e))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+    return writer.write_image_data(rgba).map_err(|e| format!("failed to write {}: {}", path.display(), e));
+}
+
+// saves rendered frames as a numbered png sequence and/or pipes them as raw RGBA to an encoder such as ffmpeg
+pub struct FrameDumper {
+    pub width: u32,
+    pub height: u32,
+    pub png_dir: Option<PathBuf>, // frames are written to png_dir/frame_00000.png etc.
+    encoder: Option<Child>,
+    pub frame: usize,
+}
+
+impl FrameDumper {
+    pub fn new(width: u32, height: u32) -> FrameDumper {
+        FrameDumper {
+            width,
+            height,
+            png_dir: None,
+            encoder: None,
+            frame: 0
+        }
+    }
+
+    pub fn set_png_dir(&mut self, dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+        self.png_dir = Some(dir.to_path_buf());
+        return Ok(());
+    }
+
+    // the command is run by the shell with raw RGBA frames on its stdin
+    // {width} and {height} are replaced with the frame size, eg.
+    // ffmpeg -f rawvideo -pix_fmt rgba -s {width}x{height} -r 60 -i - out.mp4
+    pub fn set_encoder(&mut self, command: &str) -> Result<(), String> {
+        let command = command.replace("{width}", &self.width.to_string()).replace("{height}", &self.height.to_string());
+        let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+        let child = Command::new(shell)
+            .arg(flag)
+            .arg(&command)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("failed to run encoder '{}': {}", command, e))?;
+        self.encoder = Some(child);
+        return Ok(());
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        return self.png_dir.is_some() || self.encoder.is_some();
+    }
+
+    pub fn write_frame(&mut self, rgba: &[u8]) -> Result<(), String> {
+        let expected = (self.width * self.height * 4) as usize;
+        if rgba.len() != expected {
+            return Err(format!("frame is {} bytes, expected {} for {}x{} RGBA", rgba.len(), expected, self.width, self.height));
+        }
+
+        if let Some(dir) = &self.png_dir {
+            write_png(&dir.join(format!("frame_{:05}.png", self.frame)), self.width, self.height, rgba)?;
+        }
+
+        if let Some(encoder) = &mut self.encoder {
+            let stdin = encoder.stdin.as_mut().ok_or("encoder has no stdin".to_string())?;
+            stdin.write_all(rgba).map_err(|e| format!("failed to write to the encoder: {}", e))?;
+        }
+
+        self.frame += 1;
+        return Ok(());
+    }
+
+    // closes the encoders input so it can finish, then waits for it
+    pub fn finish(mut self) -> Result<(), String> {
+        if let Some(mut encoder) = self.encoder.take() {
+            drop(encoder.stdin.take());
+            let status = encoder.wait().map_err(|e| format!("failed to wait for the encoder: {}", e))?;
+            if !status.success() {
+                return Err(format!("encoder failed with {}", status));
+            }
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use crate::frame_dumper::FrameDumper;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        return std::env::temp_dir().join(format!("fluidic_space_{}_{}", name, std::process::id()));
+    }
+
+    #[test]
+    fn rejects_a_wrongly_sized_frame() {
+        let dir = temp_dir("wrong_size");
+        let mut frame_dumper = FrameDumper::new(4, 2);
+        frame_dumper.set_png_dir(&dir).unwrap();
+
+        assert!(frame_dumper.write_frame(&[0; 4 * 2 * 3]).is_err());
+        assert!(frame_dumper.write_frame(&[0; 4 * 2 * 4 + 1]).is_err());
+        assert_eq!(frame_dumper.frame, 0);
+        assert!(!dir.join("frame_00000.png").exists());
+
+        frame_dumper.write_frame(&[255; 4 * 2 * 4]).unwrap();
+        assert_eq!(frame_dumper.frame, 1);
+        assert!(dir.join("frame_00000.png").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pipes_frames_to_the_encoder() {
+        let dir = temp_dir("encoder");
+        fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("frames.raw");
+        let mut frame_dumper = FrameDumper::new(4, 2);
+        frame_dumper.set_encoder(&format!("cat > {}", output.display())).unwrap();
+
+        frame_dumper.write_frame(&[1; 4 * 2 * 4]).unwrap();
+        frame_dumper.write_frame(&[2; 4 * 2 * 4]).unwrap();
+        frame_dumper.finish().unwrap();
+
+        let data = fs::read(&output).unwrap();
+        assert_eq!(data.len(), 2 * 4 * 2 * 4);
+        assert_eq!(data[0], 1);
+        assert_eq!(data[data.len() - 1], 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
\ No newline at end of file
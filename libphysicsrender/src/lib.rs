@@ -1,7 +1,25 @@
 #![feature(portable_simd)]
 
+pub use crate::fluid_sim_renderer::{FluidSimRenderer, RenderMode};
+pub use crate::metaball::{MetaballStyle, MetaballField};
+pub use crate::colour_map::{ColourMap, ColourMode, ColourRamp, Legend};
+pub use crate::camera::{Camera, ViewportFit};
+pub use crate::debug_overlay::{DebugOverlay, DebugShape};
+pub use crate::software_renderer::{SoftwareRenderer, Rgba};
+pub use crate::frame_dumper::{FrameDumper, write_png};
+#[cfg(feature = "sdl")]
 pub use crate::sdl_fluid_sim_renderer::SdlFluidSimRenderer;
+#[cfg(feature = "sdl")]
 pub use crate::sdl_system::SdlSystem;
 
+mod fluid_sim_renderer;
+mod metaball;
+mod colour_map;
+mod camera;
+mod debug_overlay;
+mod software_renderer;
+mod frame_dumper;
+#[cfg(feature = "sdl")]
 mod sdl_fluid_sim_renderer;
+#[cfg(feature = "sdl")]
 mod sdl_system;
\ No newline at end of file
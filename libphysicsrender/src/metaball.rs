@@ -0,0 +1,121 @@
+use libphysics::FluidSim;
+use libphysics::*;
+
+use crate::software_renderer::Rgba;
+
+// https://john-wigg.dev/2DMetaballs/
+// each particle adds a linear falloff to a density field, the same falloff as assets/gradient_linear.png,
+// anything above the threshold is liquid. deeper liquid is darker and fast moving liquid turns to foam
+pub struct MetaballStyle {
+    pub radius: f32, // falloff radius in particle radii
+    pub threshold: f32, // density of the surface, a lone particle has a density of 1 at its centre
+    pub depth: f32, // density above the threshold where the colour reaches deep
+    pub max_speed: f32, // speed at which the liquid is fully foam
+    pub shallow: Rgba,
+    pub deep: Rgba,
+    pub foam: Rgba,
+}
+
+impl MetaballStyle {
+    pub fn new() -> MetaballStyle {
+        MetaballStyle {
+            radius: 2.5,
+            threshold: 0.6,
+            depth: 2.0,
+            max_speed: 20.0,
+            shallow: [80, 170, 255, 255],
+            deep: [10, 40, 140, 255],
+            foam: [230, 240, 255, 255]
+        }
+    }
+
+    // speed is 0 to 1 of the max speed
+    // the alpha ramps up just over the threshold to smooth the edge
+    pub fn shade(&self, density: f32, speed: f32) -> Rgba {
+        if density < self.threshold {
+            return [0, 0, 0, 0];
+        }
+
+        let depth = ((density - self.threshold) / self.depth).min(1.0);
+        let foam = speed.max(0.0).min(1.0);
+        let edge = ((density - self.threshold) / (self.threshold * 0.25)).min(1.0);
+
+        let mut colour: Rgba = [0, 0, 0, 0];
+        for c in 0..3 {
+            let water = self.shallow[c] as f32 + (self.deep[c] as f32 - self.shallow[c] as f32) * depth;
+            colour[c] = (water + (self.foam[c] as f32 - water) * foam) as u8;
+        }
+        colour[3] = (edge * 255.0) as u8;
+        return colour;
+    }
+}
+
+// the density field on the cpu, used by the software renderer
+pub struct MetaballField {
+    pub width: usize,
+    pub height: usize,
+    pub density: Vec<f32>,
+    pub speed: Vec<f32>, // density weighted, 0 to 1 of the max speed
+}
+
+impl MetaballField {
+    pub fn new(width: usize, height: usize) -> MetaballField {
+        MetaballField {
+            width,
+            height,
+            density: vec![0.0; width * height],
+            speed: vec![0.0; width * height]
+        }
+    }
+
+    // gases are left out, they are drawn as puffs instead
+    pub fn accumulate(&mut self, fluid_sim: &FluidSim, style: &MetaballStyle, scale: f32, offset: f32x2) {
+        for i in 0..self.density.len() {
+            self.density[i] = 0.0;
+            self.speed[i] = 0.0;
+        }
+
+        let radius = style.radius * fluid_sim.properties.radius * scale;
+        if radius <= 0.0 {
+            return;
+        }
+
+        for particle in fluid_sim.particles.iter() {
+            if fluid_sim.materials[particle.material].is_gas() {
+                continue;
+            }
+
+            let pos = particle.pos * vec2_from_single(scale) + offset;
+            let speed = (length_squared(particle.vel).sqrt() / style.max_speed).min(1.0);
+
+            let min_x = (pos[0] - radius).floor().max(0.0) as usize;
+            let min_y = (pos[1] - radius).floor().max(0.0) as usize;
+            let max_x = ((pos[0] + radius).ceil().max(0.0) as usize).min(self.width);
+            let max_y = ((pos[1] + radius).ceil().max(0.0) as usize).min(self.height);
+            for y in min_y..max_y {
+                for x in min_x..max_x {
+                    // sample at the pixel centre
+                    let delta = vec2(x as f32 + 0.5, y as f32 + 0.5) - pos;
+                    let dist = length_squared(delta).sqrt();
+                    if dist >= radius {
+                        continue;
+                    }
+
+                    let weight = 1.0 - dist / radius;
+                    let i = x + y * self.width;
+                    self.density[i] += weight;
+                    self.speed[i] += weight * speed;
+                }
+            }
+        }
+    }
+
+    pub fn shade(&self, x: usize, y: usize, style: &MetaballStyle) -> Rgba {
+        let i = x + y * self.width;
+        let density = self.density[i];
+        if density <= 0.0 {
+            return [0, 0, 0, 0];
+        }
+        return style.shade(density, self.speed[i] / density);
+    }
+}
\ No newline at end of file
@@ -1,135 +1,327 @@
 use sdl2::rect::Point;
 use sdl2::rect::Rect as SDLRect;
-use sdl2::pixels::Color;
-use sdl2::render::WindowCanvas;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::render::{BlendMode, Texture, WindowCanvas};
 use sdl2::gfx::primitives::DrawRenderer;
 
 use libphysics::FluidSim;
 use libphysics::*;
 
-pub struct SdlFluidSimRenderer/*<'a>*/ {
-    fluid_sim: *mut FluidSim,
-    //fluid_sim: &'a mut FluidSim,
-    canvas: *mut WindowCanvas
+use crate::fluid_sim_renderer::*;
+use crate::metaball::MetaballStyle;
+use crate::colour_map::{ColourMap, Legend};
+use crate::camera::Camera;
+use crate::debug_overlay::{DebugOverlay, DebugShape};
+
+// the centre of assets/gradient_linear.png is 239 in colour and alpha, so added with the Add blend mode
+// a lone particle puts this much in each channel, scaled by the alpha mod
+const GRADIENT_PEAK: f32 = (239.0 / 255.0) * (239.0 / 255.0);
+
+// keeps a packed liquid well under 255 so the density field doesn't saturate
+const GRADIENT_ALPHA_MOD: u8 = 96;
+
+pub struct SdlFluidSimRenderer<'a> {
+    canvas: &'a mut WindowCanvas,
+    pub capture: bool, // read back each frame into captured before it is presented
+    pub captured: Vec<u8>, // RGBA of the last frame drawn when capturing
+    pub mode: RenderMode,
+    pub metaball_style: MetaballStyle,
+    pub surface_options: SurfaceOptions,
+    pub colour_map: ColourMap, // particle colours when drawing particles
+    pub camera: Camera,
+    pub cursor: Option<(f32x2, f32)>, // world position and radius of a tool outline to draw
+    pub debug_overlay: DebugOverlay,
+    gradient: Option<Texture>, // falloff splatted per particle for metaballs
+    density: Option<Texture>, // render target the gradients are added into, red is speed and green is density
+    surface: Option<Texture>, // the shaded liquid surface
+    texture_size: (u32, u32),
 }
 
-impl SdlFluidSimRenderer/*<'_>*/ {
-    pub fn new/*<'a>*/(fluid_sim: *mut FluidSim, canvas: *mut WindowCanvas) -> SdlFluidSimRenderer/*<'a>*/ {
+impl SdlFluidSimRenderer<'_> {
+    pub fn new<'a>(canvas: &'a mut WindowCanvas) -> SdlFluidSimRenderer<'a> {
         SdlFluidSimRenderer {
-            fluid_sim,
-            //fluid_sim,
-            canvas
+            canvas,
+            capture: false,
+            captured: vec![],
+            mode: RenderMode::Particles,
+            metaball_style: MetaballStyle::new(),
+            surface_options: SurfaceOptions::new(),
+            colour_map: ColourMap::new(),
+            camera: Camera::new(),
+            cursor: None,
+            debug_overlay: DebugOverlay::new(),
+            gradient: None,
+            density: None,
+            surface: None,
+            texture_size: (0, 0)
+        }
+    }
+
+    // metaballs need a radial falloff texture such as assets/gradient_linear.png, without one particles are drawn instead
+    pub fn set_gradient_texture(&mut self, texture: Texture) {
+        if let Some(old) = self.gradient.replace(texture) {
+            unsafe { old.destroy(); }
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        return self.canvas.output_size().unwrap_or((0, 0));
+    }
+
+    // the window size in the same units as mouse events, which the camera works in
+    pub fn viewport(&self) -> (u32, u32) {
+        return self.canvas.window().size();
+    }
+}
+
+impl Drop for SdlFluidSimRenderer<'_> {
+    fn drop(&mut self) {
+        // textures are not tied to the canvas lifetime with unsafe_textures, so free them while it is still alive
+        for texture in [self.gradient.take(), self.density.take(), self.surface.take()] {
+            if let Some(texture) = texture {
+                unsafe { texture.destroy(); }
+            }
+        }
+    }
+}
+
+impl SdlFluidSimRenderer<'_> {
+    // add a gradient per particle into the density texture on the gpu, then read it back to threshold and colour it
+    // the gradient colour is modulated so green holds density and red holds density * speed
+    fn draw_metaballs(&mut self, fluid_sim: &FluidSim, scale: f32, offset: f32x2) -> Result<(), String> {
+        let (width, height) = self.canvas.output_size()?;
+        if self.texture_size != (width, height) || self.density.is_none() || self.surface.is_none() {
+            for texture in [self.density.take(), self.surface.take()] {
+                if let Some(texture) = texture {
+                    unsafe { texture.destroy(); }
+                }
+            }
+
+            let texture_creator = self.canvas.texture_creator();
+            self.density = Some(texture_creator.create_texture_target(PixelFormatEnum::ABGR8888, width, height).map_err(|e| e.to_string())?);
+            let mut surface = texture_creator.create_texture_streaming(PixelFormatEnum::ABGR8888, width, height).map_err(|e| e.to_string())?;
+            surface.set_blend_mode(BlendMode::Blend);
+            self.surface = Some(surface);
+            self.texture_size = (width, height);
+        }
+
+        let gradient = match &mut self.gradient {
+            Some(gradient) => gradient,
+            None => return Err("no gradient texture for metaballs".to_string())
+        };
+        gradient.set_blend_mode(BlendMode::Add);
+        gradient.set_alpha_mod(GRADIENT_ALPHA_MOD);
+
+        let style = &self.metaball_style;
+        let radius = style.radius * fluid_sim.properties.radius * scale;
+        let mut accumulated: Vec<u8> = vec![];
+        let mut result = Ok(());
+        self.canvas.with_texture_canvas(self.density.as_mut().unwrap(), |target| {
+            target.set_draw_color(Color::RGBA(0, 0, 0, 0));
+            target.clear();
+
+            for particle in fluid_sim.particles.iter() {
+                if fluid_sim.materials[particle.material].is_gas() {
+                    continue;
+                }
+
+                let pos = particle.pos * vec2_from_single(scale) + offset;
+                let speed = (length_squared(particle.vel).sqrt() / style.max_speed).min(1.0);
+                gradient.set_color_mod((speed * 255.0) as u8, 255, 0);
+
+                let size = (radius * 2.0).ceil() as u32;
+                let dst = SDLRect::new((pos[0] - radius) as i32, (pos[1] - radius) as i32, size, size);
+                target.copy(gradient, None, dst).ok();
+            }
+
+            match target.read_pixels(None, PixelFormatEnum::ABGR8888) {
+                Ok(pixels) => accumulated = pixels,
+                Err(e) => result = Err(e)
+            }
+        }).map_err(|e| e.to_string())?;
+        result?;
+
+        // threshold and colour the density, the alpha mod lowers what one particle adds
+        let unit = GRADIENT_PEAK * GRADIENT_ALPHA_MOD as f32;
+        let mut pixels: Vec<u8> = vec![0; accumulated.len()];
+        for (src, dst) in accumulated.chunks_exact(4).zip(pixels.chunks_exact_mut(4)) {
+            if src[1] == 0 {
+                continue;
+            }
+            let density = src[1] as f32 / unit;
+            let speed = src[0] as f32 / src[1] as f32;
+            dst.copy_from_slice(&style.shade(density, speed));
+        }
+
+        let surface = self.surface.as_mut().unwrap();
+        surface.update(None, &pixels, width as usize * 4).map_err(|e| e.to_string())?;
+        self.canvas.copy(surface, None, None)?;
+        return Ok(());
+    }
+}
+
+impl SdlFluidSimRenderer<'_> {
+    fn draw_surface(&mut self, fluid_sim: &FluidSim, scale: f32, offset: f32x2) {
+        let surface = fluid_sim.surface(&self.surface_options);
+        let to_screen = |p: f32x2| {
+            let screen = p * vec2_from_single(scale) + offset;
+            return (screen[0] as i16, screen[1] as i16);
+        };
+
+        let [r, g, b, a] = SURFACE_FILL;
+        for triangle in surface.triangles.iter() {
+            let [p0, p1, p2] = triangle.map(to_screen);
+            self.canvas.filled_trigon(p0.0, p0.1, p1.0, p1.1, p2.0, p2.1, Color::RGBA(r, g, b, a)).ok();
+        }
+
+        let [r, g, b, a] = SURFACE_OUTLINE;
+        self.canvas.set_draw_color(Color::RGBA(r, g, b, a));
+        for polyline in surface.polylines.iter() {
+            for i in 0..polyline.len() {
+                let a = to_screen(polyline[i]);
+                let b = to_screen(polyline[(i + 1) % polyline.len()]);
+                self.canvas.draw_line(Point::new(a.0 as i32, a.1 as i32), Point::new(b.0 as i32, b.1 as i32)).ok();
+            }
         }
     }
 }
 
 fn draw_rect_rotate(canvas: &mut WindowCanvas, rect: &libphysics::Rect, scale: f32, offset: f32x2) { 
-    let half_size = rect.size * vec2_from_single(0.5) * vec2_from_single(scale);
-
-    let pos = (rect.pos * vec2_from_single(scale)) + offset;
-    let top_left = pos - half_size;
-    let bottom_right = pos + half_size;
-    let top_right = vec2(bottom_right[0], top_left[1]);
-    let bottom_left = vec2(top_left[0], bottom_right[1]);
-
-	let radians = rect.rotation;
-
-    // rotate points around origin
-    let top_left_rotated = rotate_point_around(top_left, pos, radians);
-    let bottom_right_rotated = rotate_point_around(bottom_right, pos, radians);
-    let top_right_rotated = rotate_point_around(top_right, pos, radians);
-    let bottom_left_rotated = rotate_point_around(bottom_left, pos, radians);
-
-    let top_left_pt = Point::new(top_left_rotated[0] as i32, top_left_rotated[1] as i32);
-    let bottom_right_pt = Point::new(bottom_right_rotated[0] as i32, bottom_right_rotated[1] as i32);
-    let top_right_pt = Point::new(top_right_rotated[0] as i32, top_right_rotated[1] as i32);
-    let bottom_left_pt = Point::new(bottom_left_rotated[0] as i32, bottom_left_rotated[1] as i32);
-
-    canvas.draw_line(top_left_pt, top_right_pt).ok();
-    canvas.draw_line(top_right_pt, bottom_right_pt).ok();
-    canvas.draw_line(bottom_right_pt, bottom_left_pt).ok();
-    canvas.draw_line(bottom_left_pt, top_left_pt).ok();
+    let corners = rect_corners(rect, scale, offset);
+    for i in 0..corners.len() {
+        let a = corners[i];
+        let b = corners[(i + 1) % corners.len()];
+        canvas.draw_line(Point::new(a[0] as i32, a[1] as i32), Point::new(b[0] as i32, b[1] as i32)).ok();
+    }
 }
 
+fn draw_debug_shape(canvas: &mut WindowCanvas, shape: &DebugShape) {
+    match shape {
+        DebugShape::Line(a, b, [r, g, bl, al]) => {
+            canvas.line(a[0] as i16, a[1] as i16, b[0] as i16, b[1] as i16, Color::RGBA(*r, *g, *bl, *al)).ok();
+        },
+        DebugShape::Rect(min, max, [r, g, b, a]) => {
+            canvas.rectangle(min[0] as i16, min[1] as i16, max[0] as i16, max[1] as i16, Color::RGBA(*r, *g, *b, *a)).ok();
+        },
+        DebugShape::Text(pos, text, [r, g, b, a]) => {
+            canvas.string(pos[0] as i16, pos[1] as i16, text, Color::RGBA(*r, *g, *b, *a)).ok();
+        }
+    }
+}
 
-impl /*FluidSimRenderer for*/ SdlFluidSimRenderer/*<'_>*/ {
+fn draw_legend(canvas: &mut WindowCanvas, legend: &Legend) {
+    let (width, _height) = canvas.output_size().unwrap_or((0, 0));
+    let (x, mut y) = legend_origin(width);
+    let text = |canvas: &mut WindowCanvas, x: i32, y: i32, s: &str| {
+        canvas.string(x as i16, y as i16, s, Color::RGBA(255, 255, 255, 255)).ok();
+    };
 
-    /*
-    fn draw_particle(&self, particle: &Particle) {
-        println!("draw_particle");
+    match legend {
+        Legend::Ramp { title, ramp, min, max } => {
+            text(canvas, x, y, title);
+            y += LEGEND_ROW;
+            for i in 0..LEGEND_WIDTH {
+                let [r, g, b, a] = ramp.sample(i as f32 / (LEGEND_WIDTH - 1) as f32);
+                canvas.set_draw_color(Color::RGBA(r, g, b, a));
+                canvas.draw_line(Point::new(x + i, y), Point::new(x + i, y + LEGEND_SWATCH)).ok();
+            }
+            y += LEGEND_SWATCH + 4;
+            text(canvas, x, y, &format!("{}", min));
+            let max = format!("{}", max);
+            text(canvas, x + LEGEND_WIDTH - max.len() as i32 * 8, y, &max); // the gfx font is 8 pixels wide
+        },
+        Legend::Swatches { title, entries } => {
+            text(canvas, x, y, title);
+            for (name, [r, g, b, a]) in entries.iter() {
+                y += LEGEND_ROW;
+                canvas.box_(x as i16, y as i16, (x + LEGEND_SWATCH) as i16, (y + LEGEND_SWATCH) as i16, Color::RGBA(*r, *g, *b, *a)).ok();
+                text(canvas, x + LEGEND_SWATCH + 6, y + 1, name);
+            }
+        }
     }
+}
 
-    fn draw_rect(&self, rect: &Rect) {
-        println!("draw_rect");
-    }*/
-
-    pub fn draw(&self) {
-        unsafe {
-            let draw_grid: bool = false;
-
-            let canvas: &mut WindowCanvas = &mut *self.canvas;
-            let fluid_sim: &mut FluidSim = &mut *self.fluid_sim;
-        
-            // set up scaling to render the grid to fit to window height
-            let window = canvas.window();
-            let (_w_width, w_height) = window.size();
-            let padding: f32 = 20.0;
-            let x_offset: f32 = padding;
-            let y_offset: f32 = padding;
-            let scale: f32 = ((w_height as f32) - (padding * 2.0)) / (fluid_sim.spatial_hash.y_size as f32);
-            let offset = vec2(x_offset, y_offset);
-        
-            
-        
-            canvas.set_draw_color(Color::RGBA(0, 0, 0, 255));
-            canvas.clear();
-        
-            // draw the boundary
-            let width = (fluid_sim.spatial_hash.x_size as f32 * scale) as u32;
-            let height = (fluid_sim.spatial_hash.y_size as f32 * scale) as u32;
-            let rect = SDLRect::new(x_offset as i32, y_offset as i32, width, height);
-        
-            canvas.set_draw_color(Color::RGBA(255, 0, 0, 255));
-            canvas.draw_rect(rect).ok();
-
-            if draw_grid {
-                canvas.set_draw_color(Color::RGBA(255, 0, 0, 100));
-                for y in 0..fluid_sim.spatial_hash.y_size {
-                    for x in 0..fluid_sim.spatial_hash.x_size {
-                        let x_start = (x as f32 * scale + x_offset) as i32;
-                        let y_start = (y as f32 * scale + y_offset) as i32;
-        
-                        let w = (1.0 * scale) as u32;
-                        let h = (1.0 * scale) as u32;
-                        let rect = SDLRect::new(x_start, y_start, w, h);
-                        canvas.draw_rect(rect).ok();
-                    }
+impl FluidSimRenderer for SdlFluidSimRenderer<'_> {
+
+    fn draw(&mut self, fluid_sim: &FluidSim) {
+        let (scale, offset) = self.camera.transform(fluid_sim, self.viewport());
+        let x_offset = offset[0];
+        let y_offset = offset[1];
+    
+        self.canvas.set_draw_color(Color::RGBA(0, 0, 0, 255));
+        self.canvas.clear();
+
+        // the liquid surface goes underneath the outlines, falling back to particles if it can't be drawn
+        let mut draw_liquid_particles = true;
+        if self.mode == RenderMode::Metaballs && self.gradient.is_some() {
+            match self.draw_metaballs(fluid_sim, scale, offset) {
+                Ok(()) => draw_liquid_particles = false,
+                Err(e) => {
+                    println!("metaballs failed, drawing particles instead: {}", e);
+                    self.mode = RenderMode::Particles;
                 }
             }
+        }
+        else if self.mode == RenderMode::Surface {
+            self.draw_surface(fluid_sim, scale, offset);
+            draw_liquid_particles = false;
+        }
+
+        let canvas: &mut WindowCanvas = self.canvas;
+    
+        // draw the boundary
+        let width = (fluid_sim.spatial_hash.x_size as f32 * scale) as u32;
+        let height = (fluid_sim.spatial_hash.y_size as f32 * scale) as u32;
+        let rect = SDLRect::new(x_offset as i32, y_offset as i32, width, height);
+    
+        canvas.set_draw_color(Color::RGBA(255, 0, 0, 255));
+        canvas.draw_rect(rect).ok();
+
+        // draw rects
+        for rect in fluid_sim.rects.iter() {
+            draw_rect_rotate(canvas, rect, scale, offset);
+        }
             
-            // draw rects
-            for rect in fluid_sim.rects.iter() {
-                draw_rect_rotate(canvas, rect, scale, offset);
+        for particle in fluid_sim.particles.iter() {
+            let x2 = particle.pos[0] * scale + x_offset; // simd this!
+            let y2 = particle.pos[1] * scale + y_offset;
+            let radius2 = 1.0 * scale;
+
+            // gases are drawn as soft filled puffs that fade out over their lifetime
+            let material = &fluid_sim.materials[particle.material];
+            if material.is_gas() {
+                let alpha = (particle.alpha(material) * 128.0) as u8;
+                canvas.filled_circle(x2 as i16, y2 as i16, radius2 as i16, Color::RGBA(200, 200, 200, alpha)).ok();
+                continue;
             }
-                
-            // https://john-wigg.dev/2DMetaballs/
-            //let mut edge_particles: Vec<*const Particle> = Vec::new();
-            for particle in fluid_sim.particles.iter() {
-                /*
-                let is_edge = particle.contacts.len() <= 1;
-                if is_edge {
-                    edge_particles.push(&*particle);
-                }*/
-
-                let x2 = particle.pos[0] * scale + x_offset; // simd this!
-                let y2 = particle.pos[1] * scale + y_offset;
-                let radius2 = 1.0 * scale;
-        
-                let color = Color::RGBA(0, 255, 0, 255); //if is_edge { Color::RGBA(0, 0, 255, 255) } else { Color::RGBA(0, 255, 0, 255) };
-                canvas.circle(x2 as i16, y2 as i16, radius2 as i16, color).ok();
+
+            if !draw_liquid_particles {
+                continue;
             }
-        
-            canvas.present();
+    
+            let [r, g, b, a] = self.colour_map.colour(particle);
+            canvas.circle(x2 as i16, y2 as i16, radius2 as i16, Color::RGBA(r, g, b, a)).ok();
         }
+
+        for shape in self.debug_overlay.shapes(fluid_sim, scale, offset) {
+            draw_debug_shape(canvas, &shape);
+        }
+
+        if let Some((pos, radius)) = self.cursor {
+            let pos = pos * vec2_from_single(scale) + offset;
+            canvas.circle(pos[0] as i16, pos[1] as i16, (radius * scale) as i16, Color::RGBA(255, 255, 255, 160)).ok();
+        }
+
+        if let Some(legend) = self.colour_map.legend(fluid_sim) {
+            draw_legend(canvas, &legend);
+        }
+    
+        // the back buffer is undefined after presenting, so read it back first
+        if self.capture {
+            // ABGR8888 is RGBA in memory on little endian machines
+            self.captured = canvas.read_pixels(None, PixelFormatEnum::ABGR8888).unwrap_or(vec![]);
+        }
+
+        canvas.present();
     }
 }
\ No newline at end of file
@@ -44,6 +44,10 @@ impl SdlSystem {
         return self.texture_creator.load_texture(path).unwrap();
     }
 
+    pub fn try_load_texture(&self, path: &str) -> Result<Texture, String> {
+        return self.texture_creator.load_texture(path);
+    }
+
 /*
     pub fn run_event_loop<F: Fn(f32)>(&mut self, update: F) {
         let mut event_pump = self.sdl_context.event_pump().unwrap();
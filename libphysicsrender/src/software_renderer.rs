@@ -0,0 +1,352 @@
+use libphysics::FluidSim;
+use libphysics::*;
+
+use crate::fluid_sim_renderer::*;
+use crate::metaball::{MetaballField, MetaballStyle};
+use crate::colour_map::{ColourMap, Legend};
+use crate::camera::Camera;
+use crate::debug_overlay::{DebugOverlay, DebugShape};
+
+pub type Rgba = [u8; 4];
+
+// draws into an RGBA buffer on the cpu, for headless image output and tests
+// matches the look of the SdlFluidSimRenderer
+pub struct SoftwareRenderer {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>, // RGBA, row major with the top row first
+    pub mode: RenderMode,
+    pub metaball_style: MetaballStyle,
+    pub surface_options: SurfaceOptions,
+    pub colour_map: ColourMap, // particle colours when drawing particles
+    pub camera: Camera,
+    pub debug_overlay: DebugOverlay,
+    metaball_field: MetaballField,
+}
+
+impl SoftwareRenderer {
+    pub fn new(width: usize, height: usize) -> SoftwareRenderer {
+        SoftwareRenderer {
+            width,
+            height,
+            pixels: vec![0; width * height * 4],
+            mode: RenderMode::Particles,
+            metaball_style: MetaballStyle::new(),
+            surface_options: SurfaceOptions::new(),
+            colour_map: ColourMap::new(),
+            camera: Camera::new(),
+            debug_overlay: DebugOverlay::new(),
+            metaball_field: MetaballField::new(width, height)
+        }
+    }
+
+    pub fn clear(&mut self, color: Rgba) {
+        for pixel in self.pixels.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&color);
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Rgba {
+        let i = (x + y * self.width) * 4;
+        return [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]];
+    }
+
+    // alpha blended over what is already there, anything off the buffer is ignored
+    pub fn blend_pixel(&mut self, x: i32, y: i32, color: Rgba) {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return;
+        }
+
+        let i = (x as usize + y as usize * self.width) * 4;
+        let alpha = color[3] as u32;
+        for c in 0..3 {
+            let dst = self.pixels[i + c] as u32;
+            self.pixels[i + c] = ((color[c] as u32 * alpha + dst * (255 - alpha)) / 255) as u8;
+        }
+        let dst_alpha = self.pixels[i + 3] as u32;
+        self.pixels[i + 3] = (alpha + dst_alpha * (255 - alpha) / 255) as u8;
+    }
+
+    // https://en.wikipedia.org/wiki/Bresenham%27s_line_algorithm
+    pub fn draw_line(&mut self, a: f32x2, b: f32x2, color: Rgba) {
+        let (mut x0, mut y0) = (a[0] as i32, a[1] as i32);
+        let (x1, y1) = (b[0] as i32, b[1] as i32);
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let sx = if x0 < x1 { 1 } else { -1 };
+        let sy = if y0 < y1 { 1 } else { -1 };
+        let mut error = dx + dy;
+
+        loop {
+            self.blend_pixel(x0, y0, color);
+            if x0 == x1 && y0 == y1 {
+                break;
+            }
+
+            let e2 = 2 * error;
+            if e2 >= dy {
+                error += dy;
+                x0 += sx;
+            }
+            if e2 <= dx {
+                error += dx;
+                y0 += sy;
+            }
+        }
+    }
+
+    pub fn draw_rect(&mut self, min: f32x2, max: f32x2, color: Rgba) {
+        let top_right = vec2(max[0], min[1]);
+        let bottom_left = vec2(min[0], max[1]);
+        self.draw_line(min, top_right, color);
+        self.draw_line(top_right, max, color);
+        self.draw_line(max, bottom_left, color);
+        self.draw_line(bottom_left, min, color);
+    }
+
+    // https://en.wikipedia.org/wiki/Midpoint_circle_algorithm
+    pub fn draw_circle(&mut self, centre: f32x2, radius: f32, color: Rgba) {
+        let (cx, cy) = (centre[0] as i32, centre[1] as i32);
+        let radius = radius as i32;
+        let mut x = radius;
+        let mut y = 0;
+        let mut error = 1 - radius;
+
+        // the octants overlap on the diagonals and axes, so collect the points first to avoid blending them twice
+        let mut points: Vec<(i32, i32)> = Vec::new();
+        while x >= y {
+            for (px, py) in [(x, y), (y, x), (-y, x), (-x, y), (-x, -y), (-y, -x), (y, -x), (x, -y)] {
+                points.push((cx + px, cy + py));
+            }
+
+            y += 1;
+            if error < 0 {
+                error += 2 * y + 1;
+            }
+            else {
+                x -= 1;
+                error += 2 * (y - x) + 1;
+            }
+        }
+
+        points.sort();
+        points.dedup();
+        for (px, py) in points {
+            self.blend_pixel(px, py, color);
+        }
+    }
+
+    pub fn fill_circle(&mut self, centre: f32x2, radius: f32, color: Rgba) {
+        let (cx, cy) = (centre[0] as i32, centre[1] as i32);
+        let radius = radius as i32;
+        let radius_sqrd = radius * radius;
+        for y in -radius..=radius {
+            for x in -radius..=radius {
+                if x * x + y * y <= radius_sqrd {
+                    self.blend_pixel(cx + x, cy + y, color);
+                }
+            }
+        }
+    }
+
+    // fills pixels whose centre is inside the triangle, either winding
+    pub fn fill_triangle(&mut self, points: [f32x2; 3], color: Rgba) {
+        let cross = |a: f32x2, b: f32x2, p: f32x2| (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0]);
+        let min_x = points.iter().map(|p| p[0]).fold(f32::MAX, f32::min).floor().max(0.0) as i32;
+        let min_y = points.iter().map(|p| p[1]).fold(f32::MAX, f32::min).floor().max(0.0) as i32;
+        let max_x = points.iter().map(|p| p[0]).fold(f32::MIN, f32::max).ceil().min(self.width as f32) as i32;
+        let max_y = points.iter().map(|p| p[1]).fold(f32::MIN, f32::max).ceil().min(self.height as f32) as i32;
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let p = vec2(x as f32 + 0.5, y as f32 + 0.5);
+                let d0 = cross(points[0], points[1], p);
+                let d1 = cross(points[1], points[2], p);
+                let d2 = cross(points[2], points[0], p);
+                let inside = (d0 >= 0.0 && d1 >= 0.0 && d2 >= 0.0) || (d0 <= 0.0 && d1 <= 0.0 && d2 <= 0.0);
+                if inside {
+                    self.blend_pixel(x, y, color);
+                }
+            }
+        }
+    }
+}
+
+impl SoftwareRenderer {
+    // there is no font, so only the colours are drawn, laid out the same as the SdlFluidSimRenderer legend
+    fn draw_legend(&mut self, legend: &Legend) {
+        let (x, y) = legend_origin(self.width as u32);
+        let y = y + LEGEND_ROW;
+        match legend {
+            Legend::Ramp { ramp, .. } => {
+                for i in 0..LEGEND_WIDTH {
+                    let color = ramp.sample(i as f32 / (LEGEND_WIDTH - 1) as f32);
+                    let column = (x + i) as f32;
+                    self.draw_line(vec2(column, y as f32), vec2(column, (y + LEGEND_SWATCH) as f32), color);
+                }
+            },
+            Legend::Swatches { entries, .. } => {
+                for (i, (_name, color)) in entries.iter().enumerate() {
+                    let top = y + i as i32 * LEGEND_ROW;
+                    for row in top..=top + LEGEND_SWATCH {
+                        self.draw_line(vec2(x as f32, row as f32), vec2((x + LEGEND_SWATCH) as f32, row as f32), *color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl FluidSimRenderer for SoftwareRenderer {
+    fn draw(&mut self, fluid_sim: &FluidSim) {
+        let (scale, offset) = self.camera.transform(fluid_sim, (self.width as u32, self.height as u32));
+
+        self.clear([0, 0, 0, 255]);
+
+        // the liquid surface goes underneath the outlines
+        if self.mode == RenderMode::Metaballs {
+            self.metaball_field.accumulate(fluid_sim, &self.metaball_style, scale, offset);
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    let color = self.metaball_field.shade(x, y, &self.metaball_style);
+                    if color[3] > 0 {
+                        self.blend_pixel(x as i32, y as i32, color);
+                    }
+                }
+            }
+        }
+        else if self.mode == RenderMode::Surface {
+            let surface = fluid_sim.surface(&self.surface_options);
+            let to_screen = |p: f32x2| p * vec2_from_single(scale) + offset;
+            for triangle in surface.triangles.iter() {
+                self.fill_triangle(triangle.map(to_screen), SURFACE_FILL);
+            }
+            for polyline in surface.polylines.iter() {
+                for i in 0..polyline.len() {
+                    self.draw_line(to_screen(polyline[i]), to_screen(polyline[(i + 1) % polyline.len()]), SURFACE_OUTLINE);
+                }
+            }
+        }
+
+        // draw the boundary
+        let size = vec2(fluid_sim.spatial_hash.x_size as f32, fluid_sim.spatial_hash.y_size as f32) * vec2_from_single(scale);
+        self.draw_rect(offset, offset + size, [255, 0, 0, 255]);
+
+        // draw rects
+        for rect in fluid_sim.rects.iter() {
+            let corners = rect_corners(rect, scale, offset);
+            for i in 0..corners.len() {
+                self.draw_line(corners[i], corners[(i + 1) % corners.len()], [255, 0, 0, 255]);
+            }
+        }
+
+        for particle in fluid_sim.particles.iter() {
+            let pos = particle.pos * vec2_from_single(scale) + offset;
+            let radius = 1.0 * scale;
+
+            // gases are drawn as soft filled puffs that fade out over their lifetime
+            let material = &fluid_sim.materials[particle.material];
+            if material.is_gas() {
+                let alpha = (particle.alpha(material) * 128.0) as u8;
+                self.fill_circle(pos, radius, [200, 200, 200, alpha]);
+                continue;
+            }
+
+            if self.mode != RenderMode::Particles {
+                continue;
+            }
+
+            let color = self.colour_map.colour(particle);
+            self.draw_circle(pos, radius, color);
+        }
+
+        // there is no font for the text
+        for shape in self.debug_overlay.shapes(fluid_sim, scale, offset) {
+            match shape {
+                DebugShape::Line(a, b, color) => self.draw_line(a, b, color),
+                DebugShape::Rect(min, max, color) => self.draw_rect(min, max, color),
+                DebugShape::Text(..) => {}
+            }
+        }
+
+        if let Some(legend) = self.colour_map.legend(fluid_sim) {
+            self.draw_legend(&legend);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libphysics::*;
+    use crate::fluid_sim_renderer::FluidSimRenderer;
+    use crate::software_renderer::SoftwareRenderer;
+
+    const BLACK: [u8; 4] = [0, 0, 0, 255];
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+
+    #[test]
+    fn draws_the_boundary() {
+        let fluid_sim = FluidSim::with_seed(10, 10, 0);
+        let mut renderer = SoftwareRenderer::new(200, 200);
+        renderer.draw(&fluid_sim);
+
+        let (scale, offset) = renderer.camera.transform(&fluid_sim, (200, 200));
+        let (min_x, min_y) = (offset[0] as usize, offset[1] as usize);
+        let (max_x, max_y) = ((offset[0] + 10.0 * scale) as usize, (offset[1] + 10.0 * scale) as usize);
+        assert_eq!(renderer.pixel(min_x, min_y), RED);
+        assert_eq!(renderer.pixel(max_x, max_y), RED);
+        assert_eq!(renderer.pixel(min_x, (min_y + max_y) / 2), RED);
+        assert_eq!(renderer.pixel((min_x + max_x) / 2, max_y), RED);
+        assert_eq!(renderer.pixel((min_x + max_x) / 2, (min_y + max_y) / 2), BLACK);
+        assert_eq!(renderer.pixel(min_x - 2, min_y - 2), BLACK);
+    }
+
+    #[test]
+    fn draws_particles_where_the_camera_puts_them() {
+        let mut fluid_sim = FluidSim::with_seed(10, 10, 0);
+        fluid_sim.add_particles(&vec![Particle::new(vec2(5.0, 5.0))]);
+        let mut renderer = SoftwareRenderer::new(200, 200);
+        renderer.camera.zoom = 2.0;
+        renderer.camera.pos = vec2(3.0, 2.0);
+        renderer.draw(&fluid_sim);
+
+        let scale = renderer.camera.scale(&fluid_sim, (200, 200));
+        let centre = renderer.camera.world_to_screen(&fluid_sim, (200, 200), vec2(5.0, 5.0));
+        let (x, y, radius) = (centre[0] as usize, centre[1] as usize, scale as usize);
+
+        // the particle is an outline of radius 1 in world units
+        assert_eq!(renderer.pixel(x + radius, y), GREEN);
+        assert_eq!(renderer.pixel(x - radius, y), GREEN);
+        assert_eq!(renderer.pixel(x, y + radius), GREEN);
+        assert_eq!(renderer.pixel(x, y - radius), GREEN);
+        assert_eq!(renderer.pixel(x, y), BLACK);
+        assert_eq!(renderer.pixel(x + radius + 2, y), BLACK);
+    }
+
+    #[test]
+    fn blend_pixel_mixes_by_alpha() {
+        let mut renderer = SoftwareRenderer::new(4, 4);
+        renderer.clear(BLACK);
+
+        renderer.blend_pixel(0, 0, [255, 0, 0, 128]);
+        assert_eq!(renderer.pixel(0, 0), [128, 0, 0, 255]);
+
+        renderer.blend_pixel(1, 0, [255, 0, 0, 0]);
+        assert_eq!(renderer.pixel(1, 0), BLACK);
+
+        renderer.blend_pixel(2, 0, [0, 255, 0, 255]);
+        assert_eq!(renderer.pixel(2, 0), GREEN);
+
+        // the destination alpha builds up towards opaque
+        renderer.clear([0, 0, 0, 0]);
+        renderer.blend_pixel(0, 0, [255, 255, 255, 128]);
+        assert_eq!(renderer.pixel(0, 0), [128, 128, 128, 128]);
+        renderer.blend_pixel(0, 0, [255, 255, 255, 128]);
+        assert_eq!(renderer.pixel(0, 0), [191, 191, 191, 191]);
+
+        // anything off the buffer is ignored
+        renderer.blend_pixel(-1, 0, RED);
+        renderer.blend_pixel(0, 4, RED);
+        assert!(renderer.pixels.chunks_exact(4).all(|pixel| pixel != RED));
+    }
+}
\ No newline at end of file
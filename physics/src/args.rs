@@ -0,0 +1,181 @@
+use std::str::FromStr;
+use libphysics::ExportFormat;
+use libphysicsrender::{RenderMode, ColourMode};
+
+// command line options
+//
+//   physics [scene] [--size n] [--particles n] [--record cache] [--quantise] [--play cache] [--steps n] [--dt seconds] [--export dir] [--format vtk|ply|csv]
+//           [--png dir] [--encode command] [--width n] [--height n] [--render mode] [--colour mode] [--debug] [--bench]
+
+pub struct Args {
+    pub scene: Option<String>, // scene file to load instead of the built in scene
+    pub size: usize, // world width and height of the built in scene
+    pub particles: usize, // number of particles in the built in scene
+    pub record: Option<String>, // write every simulated frame to this particle cache
+    pub quantise: bool, // record a smaller, lossy cache
+    pub play: Option<String>, // render from this particle cache instead of simulating
+    pub steps: Option<usize>, // run this many steps without a window then exit
+    pub dt: f32, // fixed time step when running headless
+    pub export: Option<String>, // directory to write a file per step to when running headless
+    pub format: ExportFormat,
+    pub png: Option<String>, // directory to save each rendered frame to
+    pub encode: Option<String>, // shell command to pipe raw RGBA frames to
+    pub width: u32, // window size, and image size when rendering without a window
+    pub height: u32,
+    pub render: RenderMode, // how liquids are drawn
+    pub colour: ColourMode, // what particles are coloured by when drawing particles
+    pub debug: bool, // start with the debug overlay on
+    pub bench: bool, // time a single step of a large random scene then exit
+}
+
+pub const USAGE: &str = "usage: physics [scene] [--size <n>] [--particles <n>] [--record <cache>] [--quantise] [--play <cache>]
+               [--steps <n>] [--dt <seconds>] [--export <dir>] [--format <vtk|ply|csv>]
+               [--png <dir>] [--encode <command>] [--width <n>] [--height <n>] [--render <mode>] [--colour <mode>] [--debug] [--bench]
+
+  scene              ron, json or toml scene file, the built in scene is used if not given
+  --size <n>         world width and height of the built in scene, defaults to 100
+  --particles <n>    number of particles in the built in scene, defaults to 400
+  --record <cache>   write each simulated frame to a particle cache
+  --quantise         store the recorded positions and velocities in 16 bits
+  --play <cache>     play back a particle cache instead of simulating, the scene only provides the shapes
+  --steps <n>        run n steps without opening a window, then exit
+  --dt <seconds>     time step when running without a window, defaults to 0.01
+  --export <dir>     write the particles after each step to dir/frame_00000.vtk etc. when running without a window
+  --format <format>  export format: vtk, ply or csv, defaults to vtk
+  --png <dir>        save each rendered frame to dir/frame_00000.png etc.
+  --encode <command> pipe each rendered frame as raw RGBA to a shell command, {width} and {height} are replaced
+                     eg. --encode \"ffmpeg -f rawvideo -pix_fmt rgba -s {width}x{height} -r 60 -i - out.mp4\"
+  --width <n>        window width, and image width when rendering without a window, defaults to 800
+  --height <n>       window height, and image height when rendering without a window, defaults to 600
+  --render <mode>    how liquids are drawn: particles, metaballs or surface, defaults to particles, cycle with M in the window
+  --colour <mode>    colour particles by: solid, speed, contacts, density, material, temperature or surface,
+                     defaults to solid, cycle with C in the window
+  --debug            draw the spatial hash, contacts, velocities, bounding boxes and collision normals, toggle with D in the window
+  --bench            time one step of 2000 random particles in a 300x300 world, then exit
+  --help             show this message";
+
+impl Args {
+    pub fn new() -> Args {
+        Args {
+            scene: None,
+            size: 100,
+            particles: 400,
+            record: None,
+            quantise: false,
+            play: None,
+            steps: None,
+            dt: 0.01,
+            export: None,
+            format: ExportFormat::Vtk,
+            png: None,
+            encode: None,
+            width: 800,
+            height: 600,
+            render: RenderMode::Particles,
+            colour: ColourMode::Solid,
+            debug: false,
+            bench: false
+        }
+    }
+
+    // args should not include the program name
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Args, String> {
+        let mut result = Args::new();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--size" => result.size = Args::number(&arg, args.next())?,
+                "--particles" => result.particles = Args::number(&arg, args.next())?,
+                "--record" => result.record = Some(Args::value(&arg, args.next())?),
+                "--quantise" => result.quantise = true,
+                "--play" => result.play = Some(Args::value(&arg, args.next())?),
+                "--steps" => result.steps = Some(Args::number(&arg, args.next())?),
+                "--dt" => result.dt = Args::number(&arg, args.next())?,
+                "--png" => result.png = Some(Args::value(&arg, args.next())?),
+                "--encode" => result.encode = Some(Args::value(&arg, args.next())?),
+                "--width" => result.width = Args::number(&arg, args.next())?,
+                "--height" => result.height = Args::number(&arg, args.next())?,
+                "--render" => {
+                    let mode = Args::value(&arg, args.next())?;
+                    result.render = match mode.as_str() {
+                        "particles" => RenderMode::Particles,
+                        "metaballs" => RenderMode::Metaballs,
+                        "surface" => RenderMode::Surface,
+                        _ => return Err(format!("unknown render mode '{}', expected particles, metaballs or surface", mode))
+                    };
+                },
+                "--colour" => {
+                    let mode = Args::value(&arg, args.next())?;
+                    result.colour = ColourMode::from_name(&mode).ok_or(format!("unknown colour mode '{}', expected solid, speed, contacts, density, material, temperature or surface", mode))?;
+                },
+                "--debug" => result.debug = true,
+                "--bench" => result.bench = true,
+                "--export" => result.export = Some(Args::value(&arg, args.next())?),
+                "--format" => {
+                    let format = Args::value(&arg, args.next())?;
+                    result.format = ExportFormat::from_extension(&format).ok_or(format!("unknown export format '{}', expected vtk, ply or csv", format))?;
+                },
+                "--help" | "-h" => return Err(USAGE.to_string()),
+                _ if arg.starts_with("-") => return Err(format!("unknown option '{}'\n\n{}", arg, USAGE)),
+                _ => {
+                    if result.scene.is_some() {
+                        return Err(format!("only one scene can be given\n\n{}", USAGE));
+                    }
+                    result.scene = Some(arg);
+                }
+            }
+        }
+
+        if result.record.is_some() && result.play.is_some() {
+            return Err("--record and --play can't be used together".to_string());
+        }
+
+        if result.steps.is_some() && result.play.is_some() {
+            return Err("--steps and --play can't be used together".to_string());
+        }
+
+        if result.export.is_some() && result.steps.is_none() {
+            return Err("--export needs --steps".to_string());
+        }
+
+        if result.width == 0 || result.height == 0 {
+            return Err(format!("--width and --height must be at least 1, got {}x{}", result.width, result.height));
+        }
+
+        if result.size == 0 {
+            return Err("--size must be at least 1".to_string());
+        }
+
+        // NaN and inf aren't finite, and would otherwise get past the comparison
+        if !result.dt.is_finite() || result.dt <= 0.0 {
+            return Err(format!("--dt must be positive and finite, got {}", result.dt));
+        }
+
+        return Ok(result);
+    }
+
+    fn value(option: &str, value: Option<String>) -> Result<String, String> {
+        return value.ok_or(format!("{} needs a value\n\n{}", option, USAGE));
+    }
+
+    fn number<T: FromStr>(option: &str, value: Option<String>) -> Result<T, String> {
+        let value = Args::value(option, value)?;
+        return value.parse::<T>().map_err(|_| format!("{} needs a number, got '{}'", option, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::args::Args;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        return Args::parse(args.iter().map(|arg| arg.to_string()));
+    }
+
+    #[test]
+    fn dt_must_be_positive_and_finite() {
+        assert_eq!(parse(&["--dt", "0.02"]).unwrap().dt, 0.02);
+        for dt in ["0", "-1", "NaN", "inf"] {
+            assert!(parse(&["--dt", dt]).is_err(), "--dt {} was accepted", dt);
+        }
+    }
+}
\ No newline at end of file
@@ -4,24 +4,32 @@
 #![feature(test)]
 #![feature(nll)]
 
+#[cfg(feature = "sdl")]
 use sdl2::event::Event;
+#[cfg(feature = "sdl")]
 use sdl2::keyboard::Keycode;
+#[cfg(feature = "sdl")]
+use sdl2::mouse::MouseButton;
 //use std::time::Duration;
 use core_simd::*;
 use std::time::Instant;
+use std::path::Path;
 
 use libphysics::*;
 use libphysicsrender::*;
 
-fn main() -> Result<(), String> {
-    //basic_fluid::init_world();
-    libphysics::test();
+use crate::args::Args;
+#[cfg(feature = "sdl")]
+use crate::tools::{Tool, Tools};
 
-    const GRID_SIZE: usize = 100;
-    const PARTICLE_COUNT: usize = 400;
-    //const SLEEP_PER_FRAME_MS: u64 = 0;
+mod args;
+// only used by the window, but has no sdl code so is tested without it
+#[cfg(any(feature = "sdl", test))]
+#[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+mod tools;
 
-    let mut fluid_sim = FluidSim::new(GRID_SIZE, GRID_SIZE);
+fn default_scene(grid_size: usize, particle_count: usize) -> FluidSim {
+    let mut fluid_sim = FluidSim::new(grid_size, grid_size);
 
     //fluid_sim.collision_energy_loss = 0.5;
     //fluid_sim.elasticity = 20.0;
@@ -43,7 +51,8 @@ fn main() -> Result<(), String> {
         libphysics::Rect {
             pos: Simd::from_array([30.0, 50.0]),
             size: Simd::from_array([30.0, 10.0]),
-            rotation: (20.0 as f32).to_radians()
+            rotation: (20.0 as f32).to_radians(),
+            temperature: None
         }
     );
 
@@ -51,23 +60,183 @@ fn main() -> Result<(), String> {
         libphysics::Rect {
             pos: Simd::from_array([70.0, 50.0]),
             size: Simd::from_array([30.0, 10.0]),
-            rotation: (-20.0 as f32).to_radians()
+            rotation: (-20.0 as f32).to_radians(),
+            temperature: None
         }
     );
 
-    let particles = fluid_sim.generate_random_particles(PARTICLE_COUNT);
+    let particles = fluid_sim.generate_random_particles(particle_count);
 
     //let mut particles = vec![];
     //particles.push(Particle::with_vel(vec2(45.0, 30.0), vec2(0.0, 0.0)));
 
     fluid_sim.add_particles(&particles);
 
-    let mut sdl = SdlSystem::new("Fluidic Space - Fluid Dynamics", 800, 600);
-    let fluid_sim_renderer = SdlFluidSimRenderer::new(&mut fluid_sim, &mut sdl.canvas);
+    return fluid_sim;
+}
+
+fn frame_dumper(args: &Args, width: u32, height: u32) -> Result<FrameDumper, String> {
+    let mut frame_dumper = FrameDumper::new(width, height);
+    if let Some(png_dir) = &args.png {
+        frame_dumper.set_png_dir(Path::new(png_dir))?;
+    }
+    if let Some(command) = &args.encode {
+        frame_dumper.set_encoder(command)?;
+    }
+    return Ok(frame_dumper);
+}
+
+// step the sim without a window, writing out each frame
+fn run_headless(fluid_sim: &mut FluidSim, args: &Args, steps: usize, mut recorder: Option<ParticleCacheWriter>) -> Result<(), String> {
+    if let Some(export_dir) = &args.export {
+        std::fs::create_dir_all(export_dir).map_err(|e| format!("failed to create {}: {}", export_dir, e))?;
+    }
+
+    // images are drawn by the software renderer as there is no window
+    let mut frame_dumper = frame_dumper(args, args.width, args.height)?;
+    let mut renderer = SoftwareRenderer::new(args.width as usize, args.height as usize);
+    renderer.mode = args.render;
+    renderer.colour_map.mode = args.colour;
+    renderer.debug_overlay.enabled = args.debug;
+
+    let start = Instant::now();
+    for step in 0..steps {
+        fluid_sim.update(args.dt);
+
+        if frame_dumper.is_enabled() {
+            renderer.draw(fluid_sim);
+            frame_dumper.write_frame(&renderer.pixels)?;
+        }
+
+        if let Some(export_dir) = &args.export {
+            let path = Path::new(export_dir).join(format!("frame_{:05}.{}", step, args.format.extension()));
+            args.format.write_file(fluid_sim, &path)?;
+        }
+
+        if let Some(recorder) = &mut recorder {
+            recorder.write_frame(fluid_sim, (step + 1) as f32 * args.dt)?;
+        }
+    }
+
+    if let Some(recorder) = recorder {
+        recorder.finish()?;
+    }
+    frame_dumper.finish()?;
+
+    println!("{} steps of {} particles in {:?}", steps, fluid_sim.particles.len(), start.elapsed());
+    return Ok(());
+}
+
+// errors are printed as is so the usage text keeps its formatting
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    //basic_fluid::init_world();
+
+    //const SLEEP_PER_FRAME_MS: u64 = 0;
+
+    let args = Args::parse(std::env::args().skip(1))?;
+    if args.bench {
+        libphysics::test();
+        return Ok(());
+    }
+
+    // a scene file can be passed in to replace the built in scene
+    let mut fluid_sim = match &args.scene {
+        Some(scene_path) => FluidSim::load_scene(Path::new(scene_path))?,
+        None => default_scene(args.size, args.particles)
+    };
+
+    // when playing back, the particles come from the cache and the scene only provides the shapes
+    let playback = match &args.play {
+        Some(cache_path) => {
+            let reader = ParticleCacheReader::open(Path::new(cache_path))?;
+            if args.scene.is_none() {
+                fluid_sim = FluidSim::new(reader.world_size.0, reader.world_size.1);
+            }
+            if reader.world_size != (fluid_sim.spatial_hash.x_size, fluid_sim.spatial_hash.y_size) {
+                return Err(format!("{} was recorded in a {}x{} world but the scene is {}x{}, pass the scene it was recorded from",
+                    cache_path, reader.world_size.0, reader.world_size.1, fluid_sim.spatial_hash.x_size, fluid_sim.spatial_hash.y_size));
+            }
+            if reader.options.material && reader.material_count > fluid_sim.materials.len() {
+                return Err(format!("{} was recorded with {} materials but the scene has {}, pass the scene it was recorded from",
+                    cache_path, reader.material_count, fluid_sim.materials.len()));
+            }
+            Some(reader)
+        },
+        None => None
+    };
+
+    let recorder = match &args.record {
+        Some(cache_path) => {
+            let mut options = CacheOptions::new();
+            options.quantise = args.quantise;
+            Some(ParticleCacheWriter::create(Path::new(cache_path), &fluid_sim, options)?)
+        },
+        None => None
+    };
+
+    if let Some(steps) = args.steps {
+        return run_headless(&mut fluid_sim, &args, steps, recorder);
+    }
+
+    return run_window(fluid_sim, &args, playback, recorder);
+}
+
+#[cfg(not(feature = "sdl"))]
+fn run_window(_fluid_sim: FluidSim, _args: &Args, _playback: Option<ParticleCacheReader>, _recorder: Option<ParticleCacheWriter>) -> Result<(), String> {
+    return Err(format!("built without the sdl feature so there is no window, use --steps to run headless\n\n{}", args::USAGE));
+}
+
+// 1 to 9 on the main keyboard or keypad
+#[cfg(feature = "sdl")]
+fn number_key(keycode: Keycode) -> Option<usize> {
+    let keys = [
+        (Keycode::Num1, Keycode::Kp1), (Keycode::Num2, Keycode::Kp2), (Keycode::Num3, Keycode::Kp3),
+        (Keycode::Num4, Keycode::Kp4), (Keycode::Num5, Keycode::Kp5), (Keycode::Num6, Keycode::Kp6),
+        (Keycode::Num7, Keycode::Kp7), (Keycode::Num8, Keycode::Kp8), (Keycode::Num9, Keycode::Kp9)
+    ];
+    return keys.iter().position(|(num, kp)| keycode == *num || keycode == *kp).map(|i| i + 1);
+}
+
+#[cfg(feature = "sdl")]
+fn run_window(mut fluid_sim: FluidSim, args: &Args, mut playback: Option<ParticleCacheReader>, mut recorder: Option<ParticleCacheWriter>) -> Result<(), String> {
+    let mut play_time: f32 = 0.0;
+    let mut play_frame: usize = 0;
+    let mut sim_time: f32 = 0.0;
+
+    let mut sdl = SdlSystem::new("Fluidic Space - Fluid Dynamics", args.width, args.height);
+
+    // metaballs need the gradient, which is found relative to the repo root or a crate directory
+    let gradient = sdl.try_load_texture("assets/gradient_linear.png").or_else(|_| sdl.try_load_texture("../assets/gradient_linear.png"));
+
+    let mut fluid_sim_renderer = SdlFluidSimRenderer::new(&mut sdl.canvas);
+    match gradient {
+        Ok(gradient) => fluid_sim_renderer.set_gradient_texture(gradient),
+        Err(e) => println!("metaballs are not available, failed to load assets/gradient_linear.png: {}", e)
+    }
+    fluid_sim_renderer.mode = args.render;
+    fluid_sim_renderer.colour_map.mode = args.colour;
+    fluid_sim_renderer.debug_overlay.enabled = args.debug;
+
+    // frames are read back from the window, which may not be the requested size on high dpi screens
+    let (width, height) = fluid_sim_renderer.size();
+    let mut frame_dumper = frame_dumper(args, width, height)?;
+    fluid_sim_renderer.capture = frame_dumper.is_enabled();
 
     let mut event_pump = sdl.sdl_context.event_pump()?;
     let mut dt = 0.001;
 
+    // mouse is in screen space, the tools work in world space
+    let mut mouse = vec2(0.0, 0.0);
+    let mut tools = Tools::new();
+    let mut step = false;
+
     'running: loop {
         let start = Instant::now();
 
@@ -78,15 +247,139 @@ fn main() -> Result<(), String> {
                 Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
                     break 'running;
                 },
+                Event::KeyDown { keycode: Some(Keycode::M), .. } => {
+                    fluid_sim_renderer.mode = fluid_sim_renderer.mode.toggled();
+                },
+                Event::KeyDown { keycode: Some(Keycode::C), .. } => {
+                    fluid_sim_renderer.colour_map.mode = fluid_sim_renderer.colour_map.mode.next();
+                },
+                Event::KeyDown { keycode: Some(Keycode::F), .. } => {
+                    fluid_sim_renderer.camera.fit = fluid_sim_renderer.camera.fit.next();
+                },
+                Event::KeyDown { keycode: Some(Keycode::Home), .. } => {
+                    fluid_sim_renderer.camera.reset();
+                    tools.followed = None;
+                },
+                Event::KeyDown { keycode: Some(Keycode::D), .. } => {
+                    fluid_sim_renderer.debug_overlay.enabled = !fluid_sim_renderer.debug_overlay.enabled;
+                },
+                Event::KeyDown { keycode: Some(keycode @ (Keycode::F1 | Keycode::F2 | Keycode::F3 | Keycode::F4 | Keycode::F5)), .. } => {
+                    // switch the debug overlay layers on and off
+                    let overlay = &mut fluid_sim_renderer.debug_overlay;
+                    let layer = match keycode {
+                        Keycode::F1 => &mut overlay.hash_cells,
+                        Keycode::F2 => &mut overlay.contacts,
+                        Keycode::F3 => &mut overlay.velocities,
+                        Keycode::F4 => &mut overlay.aabbs,
+                        _ => &mut overlay.collision_normals
+                    };
+                    *layer = !*layer;
+                    overlay.enabled = true;
+                },
+                Event::KeyDown { keycode: Some(Keycode::Space), .. } => {
+                    tools.paused = !tools.paused;
+                    println!("{}", if tools.paused { "paused" } else { "running" });
+                },
+                Event::KeyDown { keycode: Some(Keycode::N), .. } => {
+                    // single step while paused
+                    step = tools.paused;
+                },
+                Event::KeyDown { keycode: Some(Keycode::LeftBracket), .. } => tools.resize(1.0 / 1.25),
+                Event::KeyDown { keycode: Some(Keycode::RightBracket), .. } => tools.resize(1.25),
+                Event::KeyDown { keycode: Some(Keycode::Tab), .. } => {
+                    tools.next_material(&fluid_sim);
+                    if let Some(material) = fluid_sim.materials.get(tools.material) {
+                        println!("brush material: {}", material.name);
+                    }
+                },
+                Event::KeyDown { keycode: Some(keycode), .. } if number_key(keycode).and_then(Tool::from_number).is_some() => {
+                    let tool = number_key(keycode).and_then(Tool::from_number).unwrap();
+                    tools.set_tool(tool);
+                    println!("tool: {}", tool.name());
+                },
+                Event::MouseWheel { y, .. } => {
+                    // the wheel rotates a rect being dragged, otherwise zooms
+                    if !tools.mouse_wheel(&mut fluid_sim, y) {
+                        let viewport = fluid_sim_renderer.viewport();
+                        fluid_sim_renderer.camera.zoom_at(&fluid_sim, viewport, mouse, 1.1_f32.powi(y));
+                    }
+                },
+                Event::MouseMotion { x, y, xrel, yrel, mousestate, .. } => {
+                    mouse = vec2(x as f32, y as f32);
+
+                    // drag with the right or middle button to pan, which stops following
+                    if mousestate.right() || mousestate.middle() {
+                        let viewport = fluid_sim_renderer.viewport();
+                        fluid_sim_renderer.camera.pan(&fluid_sim, viewport, vec2(xrel as f32, yrel as f32));
+                        tools.followed = None;
+                    }
+                },
+                Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => {
+                    mouse = vec2(x as f32, y as f32);
+                    let viewport = fluid_sim_renderer.viewport();
+                    tools.set_mouse(fluid_sim_renderer.camera.screen_to_world(&fluid_sim, viewport, mouse));
+                    tools.mouse_down(&fluid_sim);
+                },
+                Event::MouseButtonUp { mouse_btn: MouseButton::Left, .. } => {
+                    tools.mouse_up();
+                },
                 _ => {}
             }
         }
 
+        // edits are made even when paused so scenes can be set up before running
+        let viewport = fluid_sim_renderer.viewport();
+        tools.set_mouse(fluid_sim_renderer.camera.screen_to_world(&fluid_sim, viewport, mouse));
+        if playback.is_none() {
+            tools.update(&mut fluid_sim, dt);
+        }
+
+        // a single step uses the fixed time step
+        let running = !tools.paused || step;
+        let dt_step = if step { args.dt } else { dt };
+        step = false;
+
         // Update
-        fluid_sim.update(dt);
+        if running {
+            if let Some(reader) = &mut playback {
+                // play back in real time, looping at the end
+                if reader.frame_count() > 0 {
+                    play_time += dt_step;
+                    let mut frame = reader.read_frame(play_frame)?;
+                    while play_frame + 1 < reader.frame_count() && frame.time < play_time {
+                        play_frame += 1;
+                        frame = reader.read_frame(play_frame)?;
+                    }
+                    if play_frame + 1 >= reader.frame_count() {
+                        play_frame = 0;
+                        play_time = 0.0;
+                    }
+                    fluid_sim.particles = frame.to_particles();
+                    fluid_sim.spatial_hash_particles();
+                }
+            }
+            else {
+                fluid_sim.update(dt_step);
+                sim_time += dt_step;
+
+                if let Some(recorder) = &mut recorder {
+                    recorder.write_frame(&fluid_sim, sim_time)?;
+                }
+            }
+        }
 
         // Render
-        fluid_sim_renderer.draw();
+        if let Some(target) = &tools.followed {
+            match target.pos(&fluid_sim) {
+                Some(pos) => fluid_sim_renderer.camera.centre_on(&fluid_sim, viewport, pos),
+                None => tools.followed = None // removed by a sink or expired
+            }
+        }
+        fluid_sim_renderer.cursor = tools.cursor();
+        fluid_sim_renderer.draw(&fluid_sim);
+        if frame_dumper.is_enabled() {
+            frame_dumper.write_frame(&fluid_sim_renderer.captured)?;
+        }
 
         let duration = start.elapsed();
         dt = duration.as_nanos() as f32 / 1000000000.0;
@@ -99,5 +392,10 @@ fn main() -> Result<(), String> {
         //::std::thread::sleep(Duration::from::new(0, 1_000_000_000u32 / 60));
     }
 
+    if let Some(recorder) = recorder {
+        recorder.finish()?;
+    }
+    frame_dumper.finish()?;
+
     Ok(())
-}
+}
\ No newline at end of file
@@ -0,0 +1,267 @@
+use core_simd::*;
+use libphysics::*;
+
+// what the left mouse button does in the window, picked with the number keys
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tool {
+    Pick, // click a particle or rect for the camera to follow
+    Brush, // paint particles
+    Erase, // remove particles
+    Drag, // move rects, the mouse wheel rotates them while dragging
+    Force, // push particles along with the mouse
+}
+
+impl Tool {
+    pub const ALL: [Tool; 5] = [Tool::Pick, Tool::Brush, Tool::Erase, Tool::Drag, Tool::Force];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Tool::Pick => "pick",
+            Tool::Brush => "brush",
+            Tool::Erase => "erase",
+            Tool::Drag => "drag",
+            Tool::Force => "force"
+        };
+    }
+
+    // 1 is the first tool
+    pub fn from_number(number: usize) -> Option<Tool> {
+        if number == 0 {
+            return None;
+        }
+        return Tool::ALL.get(number - 1).copied();
+    }
+
+    pub fn has_radius(&self) -> bool {
+        return *self == Tool::Brush || *self == Tool::Erase || *self == Tool::Force;
+    }
+}
+
+// something picked with the mouse for the camera to follow
+pub enum Followed {
+    Particle(ParticleId), // by id as indices change when particles are removed
+    Rect(usize)
+}
+
+impl Followed {
+    pub fn pos(&self, fluid_sim: &FluidSim) -> Option<f32x2> {
+        return match self {
+            Followed::Particle(id) => fluid_sim.particles.iter().find(|particle| particle.id == *id).map(|particle| particle.pos),
+            Followed::Rect(i) => fluid_sim.rects.get(*i).map(|rect| rect.pos)
+        };
+    }
+}
+
+// editing the sim with the mouse, all positions are in world space
+pub struct Tools {
+    pub tool: Tool,
+    pub radius: f32, // of the brush, eraser and force
+    pub material: MaterialId, // painted by the brush
+    pub force: f32, // how quickly particles under the force tool match the mouse velocity, per second
+    pub rotate_speed: f32, // radians per mouse wheel step when rotating a rect
+    pub paused: bool,
+    pub followed: Option<Followed>,
+
+    mouse: f32x2,
+    last_mouse: f32x2,
+    pressed: bool,
+    dragging: Option<(usize, f32x2)>, // rect index and its offset from the mouse
+}
+
+impl Tools {
+    pub fn new() -> Tools {
+        Tools {
+            tool: Tool::Pick,
+            radius: 4.0,
+            material: 0,
+            force: 10.0,
+            rotate_speed: 0.1,
+            paused: false,
+            followed: None,
+            mouse: vec2(0.0, 0.0),
+            last_mouse: vec2(0.0, 0.0),
+            pressed: false,
+            dragging: None
+        }
+    }
+
+    pub fn set_tool(&mut self, tool: Tool) {
+        self.tool = tool;
+        self.pressed = false;
+        self.dragging = None;
+    }
+
+    pub fn resize(&mut self, factor: f32) {
+        self.radius = (self.radius * factor).max(0.5).min(100.0);
+    }
+
+    pub fn next_material(&mut self, fluid_sim: &FluidSim) {
+        if !fluid_sim.materials.is_empty() {
+            self.material = (self.material + 1) % fluid_sim.materials.len();
+        }
+    }
+
+    // the mouse position is set every frame as the camera can move under a still mouse
+    pub fn set_mouse(&mut self, world: f32x2) {
+        self.mouse = world;
+    }
+
+    pub fn mouse_down(&mut self, fluid_sim: &FluidSim) {
+        self.pressed = true;
+        self.last_mouse = self.mouse;
+        match self.tool {
+            Tool::Pick => self.followed = pick(fluid_sim, self.mouse),
+            Tool::Drag => self.dragging = fluid_sim.rect_at(self.mouse).map(|i| (i, fluid_sim.rects[i].pos - self.mouse)),
+            _ => {}
+        }
+    }
+
+    pub fn mouse_up(&mut self) {
+        self.pressed = false;
+        self.dragging = None;
+    }
+
+    // returns true if the wheel was used by a tool, otherwise it zooms the camera
+    pub fn mouse_wheel(&mut self, fluid_sim: &mut FluidSim, steps: i32) -> bool {
+        if let Some((i, _)) = self.dragging {
+            fluid_sim.rects[i].rotation += steps as f32 * self.rotate_speed;
+            return true;
+        }
+        return false;
+    }
+
+    // the brush, eraser and force act every frame while the button is held
+    pub fn update(&mut self, fluid_sim: &mut FluidSim, dt: f32) {
+        let mouse_delta = self.mouse - self.last_mouse;
+        self.last_mouse = self.mouse;
+        if !self.pressed {
+            return;
+        }
+
+        match self.tool {
+            Tool::Pick => {},
+            Tool::Brush => {
+                if self.material < fluid_sim.materials.len() {
+                    fluid_sim.spawn_particles_in_circle(self.mouse, self.radius, self.material);
+                }
+            },
+            Tool::Erase => {
+                fluid_sim.remove_particles_in_circle(self.mouse, self.radius);
+            },
+            Tool::Drag => {
+                if let Some((i, offset)) = self.dragging {
+                    if i < fluid_sim.rects.len() {
+                        fluid_sim.rects[i].pos = self.mouse + offset;
+                    }
+                }
+            },
+            Tool::Force => {
+                // pull velocities towards the mouse velocity, strongest at the centre
+                if dt <= 0.0 {
+                    return;
+                }
+                let mouse_vel = mouse_delta / vec2_from_single(dt);
+                let blend = 1.0 - (-self.force * dt).exp();
+                for particle in fluid_sim.particles.iter_mut() {
+                    let dist = length_squared(particle.pos - self.mouse).sqrt();
+                    if dist < self.radius {
+                        let falloff = Falloff::Linear.scale(dist, self.radius);
+                        particle.vel += (mouse_vel - particle.vel) * vec2_from_single(blend * falloff);
+                    }
+                }
+            }
+        }
+    }
+
+    // the outline to draw around the mouse
+    pub fn cursor(&self) -> Option<(f32x2, f32)> {
+        if self.tool.has_radius() {
+            return Some((self.mouse, self.radius));
+        }
+        return None;
+    }
+}
+
+// particles are picked first as they are drawn on top of rects
+fn pick(fluid_sim: &FluidSim, world: f32x2) -> Option<Followed> {
+    if let Some(i) = fluid_sim.particle_at(world, fluid_sim.properties.radius * 2.0) {
+        return Some(Followed::Particle(fluid_sim.particles[i].id));
+    }
+
+    if let Some(i) = fluid_sim.rect_at(world) {
+        return Some(Followed::Rect(i));
+    }
+
+    return None;
+}
+
+#[cfg(test)]
+mod tests {
+    use libphysics::*;
+    use crate::tools::{Tool, Tools, Followed};
+
+    fn fluid_sim() -> FluidSim {
+        let mut fluid_sim = FluidSim::with_seed(40, 40, 0);
+        fluid_sim.add_material(Material::gas("smoke"));
+        return fluid_sim;
+    }
+
+    fn click(tools: &mut Tools, fluid_sim: &mut FluidSim, world: f32x2) {
+        tools.set_mouse(world);
+        tools.mouse_down(fluid_sim);
+        tools.update(fluid_sim, 0.01);
+        tools.mouse_up();
+    }
+
+    #[test]
+    fn brush_paints_the_material_inside_the_radius() {
+        let mut fluid_sim = fluid_sim();
+        let mut tools = Tools::new();
+        tools.set_tool(Tool::Brush);
+        tools.material = 1;
+        tools.radius = 3.0;
+
+        click(&mut tools, &mut fluid_sim, vec2(20.0, 20.0));
+        let painted = fluid_sim.particles.len();
+        assert!(painted > 0);
+        for particle in fluid_sim.particles.iter() {
+            assert_eq!(particle.material, 1);
+            assert!(length_squared(particle.pos - vec2(20.0, 20.0)).sqrt() <= 3.0);
+        }
+
+        // painting over the same spot doesn't stack particles on top of each other
+        click(&mut tools, &mut fluid_sim, vec2(20.0, 20.0));
+        assert_eq!(fluid_sim.particles.len(), painted);
+
+        // nothing happens while the button is up
+        tools.set_mouse(vec2(5.0, 5.0));
+        tools.update(&mut fluid_sim, 0.01);
+        assert_eq!(fluid_sim.particles.len(), painted);
+    }
+
+    #[test]
+    fn erase_removes_only_inside_the_radius() {
+        let mut fluid_sim = fluid_sim();
+        fluid_sim.add_particles(&vec![Particle::new(vec2(10.0, 10.0)), Particle::new(vec2(11.0, 10.0)), Particle::new(vec2(30.0, 30.0))]);
+        let mut tools = Tools::new();
+        tools.set_tool(Tool::Erase);
+        tools.radius = 2.0;
+
+        click(&mut tools, &mut fluid_sim, vec2(10.5, 10.0));
+        assert_eq!(fluid_sim.particles.len(), 1);
+        assert_eq!(fluid_sim.particles[0].pos, vec2(30.0, 30.0));
+    }
+
+    #[test]
+    fn pick_follows_the_particle_under_the_mouse() {
+        let mut fluid_sim = fluid_sim();
+        fluid_sim.add_particles(&vec![Particle::new(vec2(10.0, 10.0)), Particle::new(vec2(30.0, 30.0))]);
+        let mut tools = Tools::new();
+
+        click(&mut tools, &mut fluid_sim, vec2(30.5, 30.0));
+        assert!(matches!(tools.followed, Some(Followed::Particle(1))));
+
+        click(&mut tools, &mut fluid_sim, vec2(20.0, 20.0));
+        assert!(tools.followed.is_none());
+    }
+}
\ No newline at end of file